@@ -0,0 +1,82 @@
+use util::union_find::ClassId;
+
+pub trait Column: Sized {
+    fn encode(&self) -> u32;
+    fn decode(raw: u32) -> Option<Self>;
+}
+
+impl Column for u32 {
+    fn encode(&self) -> u32 {
+        *self
+    }
+
+    fn decode(raw: u32) -> Option<Self> {
+        Some(raw)
+    }
+}
+
+impl Column for i32 {
+    fn encode(&self) -> u32 {
+        *self as u32
+    }
+
+    fn decode(raw: u32) -> Option<Self> {
+        Some(raw as i32)
+    }
+}
+
+impl Column for ClassId {
+    fn encode(&self) -> u32 {
+        self.idx()
+    }
+
+    fn decode(raw: u32) -> Option<Self> {
+        Some(ClassId::new(raw))
+    }
+}
+
+impl Column for u8 {
+    fn encode(&self) -> u32 {
+        *self as u32
+    }
+
+    fn decode(raw: u32) -> Option<Self> {
+        raw.try_into().ok()
+    }
+}
+
+impl Column for char {
+    fn encode(&self) -> u32 {
+        *self as u32
+    }
+
+    fn decode(raw: u32) -> Option<Self> {
+        char::from_u32(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_column() {
+        for c in ['a', 'Z', '\0', '\x7f', '𝄞', '\u{10FFFF}'] {
+            assert_eq!(char::decode(c.encode()), Some(c));
+        }
+        assert_eq!('𝄞'.encode(), 0x1D11E);
+        assert_eq!(char::decode(0xD800), None);
+        assert_eq!(char::decode(0xDFFF), None);
+        assert_eq!(char::decode(0x110000), None);
+    }
+
+    #[test]
+    fn byte_column() {
+        for b in [0u8, b'a', 255] {
+            assert_eq!(u8::decode(b.encode()), Some(b));
+        }
+        assert_eq!(u8::decode(256), None);
+        assert_eq!(i32::decode((-1i32).encode()), Some(-1));
+        assert_eq!(ClassId::decode(7), Some(ClassId::new(7)));
+    }
+}
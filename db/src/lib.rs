@@ -1,2 +1,3 @@
+pub mod column;
 pub mod rebuild;
 pub mod table;
@@ -1,6 +1,5 @@
-use core::hash::Hash;
 use core::mem::swap;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use util::union_find::{ClassId, UnionFind};
 
@@ -8,17 +7,34 @@ use crate::table::Table;
 
 pub trait ENode: PartialEq {
     fn root(&self) -> ClassId;
-    fn canonicalize(&self, uf: &mut UnionFind) -> Self;
+    fn operands(&self) -> Vec<ClassId>;
+    fn canonicalize(&self, uf: &UnionFind) -> Self;
 }
 
-pub fn rebuild_enode_table<const DET_COLS: usize, const DEP_COLS: usize, T, E, D>(
+pub trait Rebuildable: PartialEq + Sized {
+    fn canonical(&self, uf: &UnionFind) -> Self;
+    fn solve(&self, old: Self, uf: &mut UnionFind) -> Self;
+}
+
+impl<T: ENode> Rebuildable for T {
+    fn canonical(&self, uf: &UnionFind) -> Self {
+        self.canonicalize(uf)
+    }
+
+    fn solve(&self, old: Self, uf: &mut UnionFind) -> Self {
+        uf.merge(self.root(), old.root());
+        old
+    }
+}
+
+pub fn rebuild_table<const DET_COLS: usize, const DEP_COLS: usize, T, E, D>(
     table: &mut Table<DET_COLS, DEP_COLS>,
     uf: &mut UnionFind,
     encode: E,
     decode: D,
 ) -> bool
 where
-    T: ENode,
+    T: Rebuildable,
     E: Fn(&T) -> ([u32; DET_COLS], [u32; DEP_COLS]),
     D: Fn(&[u32; DET_COLS], &[u32; DEP_COLS]) -> T,
 {
@@ -30,16 +46,14 @@ where
         while let Some(row_id) = maybe_row_id {
             let row = table.get_row(row_id);
             let term = decode(&row.0, &row.1);
-            let canon_term = term.canonicalize(uf);
+            let canon_term = term.canonical(uf);
             if term != canon_term {
                 changed = true;
-                table.delete_row(row_id);
                 let canon_row = encode(&canon_term);
-                table.insert_row(&canon_row.0, &canon_row.1, |new, old| {
+                table.reinsert_row(row_id, &canon_row.0, &canon_row.1, |new, old| {
                     let new_term = decode(&canon_row.0, new);
                     let old_term = decode(&canon_row.0, old);
-                    uf.merge(new_term.root(), old_term.root());
-                    *old
+                    encode(&new_term.solve(old_term, uf)).1
                 });
             }
 
@@ -55,23 +69,33 @@ where
     ever_changed
 }
 
-pub fn corebuild<T>(terms: Vec<T>, uf: &mut UnionFind)
+/// Merges classes that are equal coinductively, i.e. whose terms unfold into the same (possibly
+/// cyclic) trees, and returns how many merges it made. This starts from every class being equal
+/// and splits classes until each block of the partition is closed: two classes stay together only
+/// while their sets of terms, canonicalized under the current partition, are identical. Keeping
+/// classes that merely share a term would be unsound, since a class is the set of all its terms
+/// and one shared alternative says nothing about the rest.
+pub fn corebuild<T>(terms: Vec<T>, uf: &mut UnionFind) -> u32
 where
-    T: Clone + ENode + Eq + Hash,
+    T: Clone + ENode + Ord,
 {
     let num_classes = uf.num_classes();
     let mut last_uf = UnionFind::new_all_equals(num_classes);
     let mut next_uf = UnionFind::new_all_not_equals(num_classes);
-    let mut observations = vec![HashSet::<T>::new(); num_classes as usize];
+    let mut observations = vec![BTreeSet::<T>::new(); num_classes as usize];
 
     loop {
         for term in &terms {
-            observations[term.root().idx() as usize].insert(term.canonicalize(&mut last_uf));
+            observations[term.root().idx() as usize].insert(term.canonicalize(&last_uf));
         }
 
+        // Only classes with equal observations are bisimilar. Sharing one observation isn't
+        // enough: `x = {?0, x + 0}` and `y = {?1, y + 0}` both observe `+(x, 0)` while x and y
+        // are tentatively equal, but their opaque terms still tell them apart.
         for lhs in 0..num_classes {
             for rhs in 0..num_classes {
-                if !observations[lhs as usize].is_disjoint(&observations[rhs as usize]) {
+                let lhs_obs = &observations[lhs as usize];
+                if !lhs_obs.is_empty() && *lhs_obs == observations[rhs as usize] {
                     next_uf.merge(ClassId::new(lhs), ClassId::new(rhs));
                 }
             }
@@ -83,13 +107,313 @@ where
             swap(&mut last_uf, &mut next_uf);
             next_uf = UnionFind::new_all_not_equals(num_classes);
             observations.clear();
-            observations.resize(num_classes as usize, HashSet::new());
+            observations.resize(num_classes as usize, BTreeSet::new());
         }
     }
 
+    let mut merges = 0;
     for idx in 0..num_classes {
         let id = ClassId::new(idx);
         let canon = last_uf.find(id);
+        if uf.find(id) != uf.find(canon) {
+            merges += 1;
+        }
         uf.merge(id, canon);
     }
+    merges
+}
+
+pub fn corebuild_subset<T>(terms: Vec<T>, uf: &mut UnionFind, dirty: &[ClassId]) -> u32
+where
+    T: Clone + ENode + Ord,
+{
+    let num_classes = uf.num_classes();
+    let mut reachable: BTreeSet<ClassId> = dirty.iter().map(|id| uf.find(*id)).collect();
+    loop {
+        let mut changed = false;
+        for term in &terms {
+            if term
+                .operands()
+                .into_iter()
+                .any(|id| reachable.contains(&uf.find(id)))
+            {
+                changed = reachable.insert(uf.find(term.root())) || changed;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let subset: Vec<ClassId> = (0..num_classes)
+        .map(ClassId::new)
+        .filter(|id| reachable.contains(&uf.find(*id)))
+        .collect();
+    let terms: Vec<T> = terms
+        .into_iter()
+        .filter(|term| reachable.contains(&uf.find(term.root())))
+        .collect();
+
+    if subset.is_empty() {
+        return 0;
+    }
+
+    // Only classes in the subset are ever merged, so both partitions are allocated once and just
+    // the subset is reset between rounds. Representatives are minimum ids, so comparing them over
+    // the subset compares the partitions.
+    let mut last_uf = UnionFind::new_all_not_equals(num_classes);
+    let mut next_uf = UnionFind::new_all_not_equals(num_classes);
+    for id in &subset {
+        last_uf.merge(subset[0], *id);
+    }
+    loop {
+        let mut observations = BTreeMap::<ClassId, BTreeSet<T>>::new();
+        for term in &terms {
+            observations
+                .entry(term.root())
+                .or_default()
+                .insert(term.canonicalize(&last_uf));
+        }
+
+        // See corebuild for why only equal observations merge.
+        for (lhs, lhs_obs) in &observations {
+            for (rhs, rhs_obs) in &observations {
+                if lhs_obs == rhs_obs {
+                    next_uf.merge(*lhs, *rhs);
+                }
+            }
+        }
+
+        if subset
+            .iter()
+            .all(|id| last_uf.find(*id) == next_uf.find(*id))
+        {
+            break;
+        } else {
+            swap(&mut last_uf, &mut next_uf);
+            next_uf.reset(&subset);
+        }
+    }
+
+    let mut merges = 0;
+    for id in subset {
+        let canon = last_uf.find(id);
+        if uf.find(id) != uf.find(canon) {
+            merges += 1;
+        }
+        uf.merge(id, canon);
+    }
+    merges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use util::arena::Arena;
+    use util::interner::StringInterner;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Node {
+        op: u32,
+        arg: ClassId,
+        root: ClassId,
+    }
+
+    impl ENode for Node {
+        fn root(&self) -> ClassId {
+            self.root
+        }
+
+        fn operands(&self) -> Vec<ClassId> {
+            vec![self.arg]
+        }
+
+        fn canonicalize(&self, uf: &UnionFind) -> Self {
+            Node {
+                op: self.op,
+                arg: uf.find(self.arg),
+                root: uf.find(self.root),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Bound {
+        class: ClassId,
+        bound: u32,
+    }
+
+    impl Rebuildable for Bound {
+        fn canonical(&self, uf: &UnionFind) -> Self {
+            Bound {
+                class: uf.find(self.class),
+                bound: self.bound,
+            }
+        }
+
+        fn solve(&self, old: Self, _uf: &mut UnionFind) -> Self {
+            Bound {
+                class: old.class,
+                bound: self.bound.max(old.bound),
+            }
+        }
+    }
+
+    #[test]
+    fn rebuild_table() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut uf = UnionFind::new();
+        let a = uf.makeset();
+        let b = uf.makeset();
+        let x = uf.makeset();
+        let y = uf.makeset();
+
+        let node_encode = |node: &Node| ([node.op, node.arg.idx()], [node.root.idx()]);
+        let node_decode = |det: &[u32; 2], dep: &[u32; 1]| Node {
+            op: det[0],
+            arg: ClassId::new(det[1]),
+            root: ClassId::new(dep[0]),
+        };
+        let mut nodes = Table::<2, 1>::new(interner.intern("f"));
+        for node in [
+            Node {
+                op: 0,
+                arg: a,
+                root: x,
+            },
+            Node {
+                op: 0,
+                arg: b,
+                root: y,
+            },
+        ] {
+            let (det, dep) = node_encode(&node);
+            nodes.insert_row(&det, &dep, |_, _| unreachable!());
+        }
+
+        let bound_encode = |bound: &Bound| ([bound.class.idx()], [bound.bound]);
+        let bound_decode = |det: &[u32; 1], dep: &[u32; 1]| Bound {
+            class: ClassId::new(det[0]),
+            bound: dep[0],
+        };
+        let mut bounds = Table::<1, 1>::new(interner.intern("bound"));
+        bounds.insert_row(&[a.idx()], &[3], |_, _| unreachable!());
+        bounds.insert_row(&[b.idx()], &[5], |_, _| unreachable!());
+
+        assert!(!super::rebuild_table(
+            &mut nodes,
+            &mut uf,
+            node_encode,
+            node_decode
+        ));
+        assert!(!super::rebuild_table(
+            &mut bounds,
+            &mut uf,
+            bound_encode,
+            bound_decode
+        ));
+
+        uf.merge(a, b);
+        assert!(super::rebuild_table(
+            &mut nodes,
+            &mut uf,
+            node_encode,
+            node_decode
+        ));
+        assert_eq!(uf.find(x), uf.find(y));
+        assert_eq!(nodes.iter().count(), 1);
+        assert!(super::rebuild_table(
+            &mut bounds,
+            &mut uf,
+            bound_encode,
+            bound_decode
+        ));
+        assert_eq!(bounds.iter().collect::<Vec<_>>(), vec![([a.idx()], [5])]);
+    }
+
+    #[test]
+    fn stable_ids() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut uf = UnionFind::new();
+        let a = uf.makeset();
+        let b = uf.makeset();
+        let c = uf.makeset();
+        let x = uf.makeset();
+        let y = uf.makeset();
+        let z = uf.makeset();
+
+        let node_encode = |node: &Node| ([node.op, node.arg.idx()], [node.root.idx()]);
+        let node_decode = |det: &[u32; 2], dep: &[u32; 1]| Node {
+            op: det[0],
+            arg: ClassId::new(det[1]),
+            root: ClassId::new(dep[0]),
+        };
+        let mut nodes = Table::<2, 1>::new_with_stable_ids(interner.intern("f"));
+        for (arg, root) in [(a, x), (b, y), (c, z)] {
+            let (det, dep) = node_encode(&Node { op: 0, arg, root });
+            nodes.insert_row(&det, &dep, |_, _| unreachable!());
+        }
+        let first = nodes.first_row().unwrap();
+        let second = nodes.next_row(first).unwrap();
+        let third = nodes.next_row(second).unwrap();
+        let first_id = nodes.stable_id(first).unwrap();
+        let second_id = nodes.stable_id(second).unwrap();
+        let third_id = nodes.stable_id(third).unwrap();
+        assert_ne!(first_id, second_id);
+
+        nodes.delete_row(third);
+        assert_eq!(nodes.get_by_stable_id(third_id), None);
+
+        uf.merge(a, b);
+        assert!(super::rebuild_table(
+            &mut nodes,
+            &mut uf,
+            node_encode,
+            node_decode
+        ));
+        assert_eq!(nodes.iter().count(), 1);
+        let row = nodes.get_by_stable_id(first_id).unwrap();
+        assert_eq!(nodes.get_by_stable_id(second_id), Some(row));
+        assert_eq!(
+            node_decode(&nodes.get_row(row).0, &nodes.get_row(row).1),
+            Node {
+                op: 0,
+                arg: uf.find(a),
+                root: uf.find(x),
+            }
+        );
+    }
+
+    #[test]
+    fn corebuild_distinct_observations() {
+        let mut uf = UnionFind::new();
+        let x = uf.makeset();
+        let y = uf.makeset();
+        let z = uf.makeset();
+        let w = uf.makeset();
+        let node = |op, arg| Node { op, arg, root: arg };
+
+        // x and y share f(self) but differ in their second term. z and w are both just f(self).
+        let terms = vec![
+            node(0, x),
+            node(1, x),
+            node(0, y),
+            node(2, y),
+            node(0, z),
+            node(0, w),
+        ];
+        let mut full = uf.clone();
+        assert_eq!(corebuild(terms.clone(), &mut full), 1);
+        assert_ne!(full.find(x), full.find(y));
+        assert_eq!(full.find(z), full.find(w));
+
+        let mut subset = uf.clone();
+        assert_eq!(corebuild_subset(terms, &mut subset, &[x, y, z, w]), 1);
+        assert_ne!(subset.find(x), subset.find(y));
+        assert_eq!(subset.find(z), subset.find(w));
+    }
 }
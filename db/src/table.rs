@@ -1,33 +1,253 @@
-use std::collections::HashMap;
+use core::ops::Range;
+use std::collections::{BTreeSet, HashMap};
 
+use util::bitset::BitSet;
 use util::interner::{IdentifierId, StringInterner};
+use util::union_find::ClassId;
 use util::vec::VirtualVec;
 
 const EMPTY: u32 = 0xFFFFFFFF;
 
 pub struct Table<const DET_COLS: usize, const DEP_COLS: usize> {
     contents: VirtualVec<([u32; DET_COLS], [u32; DEP_COLS])>,
+    occupied: BitSet,
     determine_map: HashMap<&'static [u32; DET_COLS], (RowId, &'static [u32; DEP_COLS])>,
 
     pub symbol: IdentifierId,
 
     pub num_allocated_rows: u32,
     pub num_free_rows: u32,
+
+    finalized: bool,
+    cons_stats: Option<(u64, u64)>,
+    merge_policy: MergePolicy<DEP_COLS>,
+    reverse_index: Option<HashMap<ClassId, Vec<RowId>>>,
+    free_rows: Option<Vec<RowId>>,
+    dep_index: Option<(usize, BTreeSet<(i64, RowId)>)>,
+    signed_cols: ([bool; DET_COLS], [bool; DEP_COLS]),
+    stable_ids: Option<StableIds>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RowId(u32);
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StableId(u64);
+
+#[derive(Clone, Debug, Default)]
+struct StableIds {
+    next: u64,
+    rows: HashMap<StableId, RowId>,
+    ids: HashMap<RowId, Vec<StableId>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MergePolicy<const DEP_COLS: usize> {
+    Union,
+    Meet(fn(&[u32; DEP_COLS], &[u32; DEP_COLS]) -> [u32; DEP_COLS]),
+}
+
 impl<const DET_COLS: usize, const DEP_COLS: usize> Table<DET_COLS, DEP_COLS> {
     pub fn new(symbol: IdentifierId) -> Self {
         Self {
             contents: VirtualVec::new(),
+            occupied: BitSet::new(),
             determine_map: HashMap::new(),
 
             symbol,
 
             num_allocated_rows: 0,
             num_free_rows: 0,
+
+            finalized: false,
+            cons_stats: None,
+            merge_policy: MergePolicy::Union,
+            reverse_index: None,
+            free_rows: None,
+            dep_index: None,
+            signed_cols: ([false; DET_COLS], [false; DEP_COLS]),
+            stable_ids: None,
+        }
+    }
+
+    pub fn new_with_signed_columns(
+        symbol: IdentifierId,
+        signed_det: [bool; DET_COLS],
+        signed_dep: [bool; DEP_COLS],
+    ) -> Self {
+        Self {
+            signed_cols: (signed_det, signed_dep),
+            ..Self::new(symbol)
+        }
+    }
+
+    pub fn new_with_free_list(symbol: IdentifierId) -> Self {
+        Self {
+            free_rows: Some(vec![]),
+            ..Self::new(symbol)
+        }
+    }
+
+    pub fn new_with_reverse_index(symbol: IdentifierId) -> Self {
+        Self {
+            reverse_index: Some(HashMap::new()),
+            ..Self::new(symbol)
+        }
+    }
+
+    pub fn new_with_stable_ids(symbol: IdentifierId) -> Self {
+        Self {
+            stable_ids: Some(StableIds::default()),
+            ..Self::new(symbol)
+        }
+    }
+
+    pub fn stable_id(&self, row: RowId) -> Option<StableId> {
+        self.stable_ids
+            .as_ref()
+            .and_then(|stable| stable.ids.get(&row))
+            .and_then(|ids| ids.first().copied())
+    }
+
+    pub fn get_by_stable_id(&self, id: StableId) -> Option<RowId> {
+        self.stable_ids
+            .as_ref()
+            .and_then(|stable| stable.rows.get(&id).copied())
+    }
+
+    fn take_stable_ids(&mut self, row: RowId) -> Vec<StableId> {
+        let Some(stable) = &mut self.stable_ids else {
+            return vec![];
+        };
+        let ids = stable.ids.remove(&row).unwrap_or_default();
+        for id in &ids {
+            stable.rows.remove(id);
+        }
+        ids
+    }
+
+    fn give_stable_ids(&mut self, row: RowId, mut carried: Vec<StableId>) {
+        let Some(stable) = &mut self.stable_ids else {
+            return;
+        };
+        if carried.is_empty() {
+            carried.push(StableId(stable.next));
+            stable.next += 1;
+        }
+        for id in &carried {
+            stable.rows.insert(*id, row);
+        }
+        stable.ids.entry(row).or_default().extend(carried);
+    }
+
+    pub fn new_with_dep_index(symbol: IdentifierId, col: usize) -> Self {
+        assert!(col < DEP_COLS, "indexed dependent column out of range");
+        Self {
+            dep_index: Some((col, BTreeSet::new())),
+            ..Self::new(symbol)
+        }
+    }
+
+    pub fn new_with_signed_dep_index(
+        symbol: IdentifierId,
+        signed_det: [bool; DET_COLS],
+        signed_dep: [bool; DEP_COLS],
+        col: usize,
+    ) -> Self {
+        Self {
+            signed_cols: (signed_det, signed_dep),
+            ..Self::new_with_dep_index(symbol, col)
+        }
+    }
+
+    /// The bounds order values as `i32`s if the column is signed and as `u32`s otherwise.
+    pub fn range_by_dep(&self, col: usize, range: Range<i64>) -> impl Iterator<Item = RowId> + '_ {
+        let Some((indexed_col, index)) = &self.dep_index else {
+            panic!("range query on table without a dependent index");
+        };
+        assert_eq!(
+            *indexed_col, col,
+            "range query on unindexed dependent column"
+        );
+        index
+            .range((range.start, RowId(0))..(range.end, RowId(0)))
+            .map(|(_, row)| *row)
+    }
+
+    pub fn rows_referencing(&self, class: ClassId) -> &[RowId] {
+        self.reverse_index
+            .as_ref()
+            .and_then(|index| index.get(&class))
+            .map_or(&[], |rows| rows)
+    }
+
+    fn index_row(&mut self, idx: usize) {
+        let row = self.contents[idx];
+        if let Some(index) = &mut self.reverse_index {
+            for col in row.0.iter().chain(row.1.iter()) {
+                let rows = index.entry(ClassId::new(*col)).or_default();
+                if rows.last() != Some(&RowId(idx as u32)) {
+                    rows.push(RowId(idx as u32));
+                }
+            }
+        }
+        if let Some((col, index)) = &mut self.dep_index {
+            let key = dep_key(self.signed_cols.1[*col], row.1[*col]);
+            index.insert((key, RowId(idx as u32)));
+        }
+    }
+
+    fn unindex_row(&mut self, idx: usize) {
+        let row = self.contents[idx];
+        if let Some(index) = &mut self.reverse_index {
+            for col in row.0.iter().chain(row.1.iter()) {
+                if let Some(rows) = index.get_mut(&ClassId::new(*col)) {
+                    rows.retain(|row| row.0 != idx as u32);
+                    if rows.is_empty() {
+                        index.remove(&ClassId::new(*col));
+                    }
+                }
+            }
+        }
+        if let Some((col, index)) = &mut self.dep_index {
+            let key = dep_key(self.signed_cols.1[*col], row.1[*col]);
+            index.remove(&(key, RowId(idx as u32)));
+        }
+        self.take_stable_ids(RowId(idx as u32));
+    }
+
+    pub fn new_with_merge_policy(
+        symbol: IdentifierId,
+        merge_policy: MergePolicy<DEP_COLS>,
+    ) -> Self {
+        Self {
+            merge_policy,
+            ..Self::new(symbol)
+        }
+    }
+
+    pub fn merge_policy(&self) -> MergePolicy<DEP_COLS> {
+        self.merge_policy
+    }
+
+    pub fn new_with_cons_stats(symbol: IdentifierId) -> Self {
+        Self {
+            cons_stats: Some((0, 0)),
+            ..Self::new(symbol)
+        }
+    }
+
+    pub fn cons_stats(&self) -> (u64, u64) {
+        self.cons_stats.unwrap_or((0, 0))
+    }
+
+    fn record_cons(&mut self, hit: bool) {
+        if let Some((hits, misses)) = &mut self.cons_stats {
+            if hit {
+                *hits += 1;
+            } else {
+                *misses += 1;
+            }
         }
     }
 
@@ -40,14 +260,101 @@ impl<const DET_COLS: usize, const DEP_COLS: usize> Table<DET_COLS, DEP_COLS> {
     where
         F: FnOnce(&[u32; DEP_COLS], &[u32; DEP_COLS]) -> [u32; DEP_COLS],
     {
+        self.insert_row_with_id(determinant, dependent, merge).1
+    }
+
+    pub fn insert_row_with_id<F>(
+        &mut self,
+        determinant: &[u32; DET_COLS],
+        dependent: &[u32; DEP_COLS],
+        merge: F,
+    ) -> (RowId, &[u32; DEP_COLS])
+    where
+        F: FnOnce(&[u32; DEP_COLS], &[u32; DEP_COLS]) -> [u32; DEP_COLS],
+    {
+        self.insert_row_carrying(determinant, dependent, merge, vec![])
+    }
+
+    pub fn reinsert_row<F>(
+        &mut self,
+        row: RowId,
+        determinant: &[u32; DET_COLS],
+        dependent: &[u32; DEP_COLS],
+        merge: F,
+    ) -> &[u32; DEP_COLS]
+    where
+        F: FnOnce(&[u32; DEP_COLS], &[u32; DEP_COLS]) -> [u32; DEP_COLS],
+    {
+        let carried = self.take_stable_ids(row);
+        self.delete_row(row);
+        self.insert_row_carrying(determinant, dependent, merge, carried)
+            .1
+    }
+
+    fn insert_row_carrying<F>(
+        &mut self,
+        determinant: &[u32; DET_COLS],
+        dependent: &[u32; DEP_COLS],
+        merge: F,
+        mut carried: Vec<StableId>,
+    ) -> (RowId, &[u32; DEP_COLS])
+    where
+        F: FnOnce(&[u32; DEP_COLS], &[u32; DEP_COLS]) -> [u32; DEP_COLS],
+    {
+        assert!(!self.finalized, "inserted into finalized table");
         if let Some(mapped_dependent) = self.determine_map.get(determinant) {
             let prior = mapped_dependent.0;
             let in_table_dep = mapped_dependent.1;
             let merged_dep = merge(dependent, in_table_dep);
+            self.record_cons(true);
+            let mut prior_ids = self.take_stable_ids(prior);
+            prior_ids.append(&mut carried);
             self.delete_row(prior);
-            self.push_row(determinant, &merged_dep)
+            self.push_row_carrying(determinant, &merged_dep, prior_ids)
+        } else {
+            self.record_cons(false);
+            self.push_row_carrying(determinant, dependent, carried)
+        }
+    }
+
+    pub fn merge_into<F>(&mut self, other: &Self, mut merge: F)
+    where
+        F: FnMut(&[u32; DEP_COLS], &[u32; DEP_COLS]) -> [u32; DEP_COLS],
+    {
+        assert_eq!(
+            self.symbol, other.symbol,
+            "merged tables with different symbols"
+        );
+        for (det, dep) in other.iter() {
+            self.insert_row(&det, &dep, &mut merge);
+        }
+    }
+
+    pub fn try_insert_new(
+        &mut self,
+        determinant: &[u32; DET_COLS],
+        dependent: &[u32; DEP_COLS],
+    ) -> Result<(), [u32; DEP_COLS]> {
+        self.try_insert_new_row(determinant, dependent)
+            .map(|_| ())
+            .map_err(|(_, dep)| dep)
+    }
+
+    /// Like `try_insert_new`, but also reports which row holds the determinant, for callers such
+    /// as `Graph::insert_get_id` that hand out row handles.
+    pub fn try_insert_new_row(
+        &mut self,
+        determinant: &[u32; DET_COLS],
+        dependent: &[u32; DEP_COLS],
+    ) -> Result<RowId, (RowId, [u32; DEP_COLS])> {
+        assert!(!self.finalized, "inserted into finalized table");
+        if let Some((row, in_table_dep)) = self.determine_map.get(determinant) {
+            let in_table = (*row, **in_table_dep);
+            self.record_cons(true);
+            Err(in_table)
         } else {
-            self.push_row(determinant, dependent)
+            self.record_cons(false);
+            Ok(self.push_row(determinant, dependent).0)
         }
     }
 
@@ -55,28 +362,41 @@ impl<const DET_COLS: usize, const DEP_COLS: usize> Table<DET_COLS, DEP_COLS> {
         &mut self,
         determinant: &[u32; DET_COLS],
         dependent: &[u32; DEP_COLS],
-    ) -> &[u32; DEP_COLS] {
+    ) -> (RowId, &[u32; DEP_COLS]) {
+        self.push_row_carrying(determinant, dependent, vec![])
+    }
+
+    fn push_row_carrying(
+        &mut self,
+        determinant: &[u32; DET_COLS],
+        dependent: &[u32; DEP_COLS],
+        carried: Vec<StableId>,
+    ) -> (RowId, &[u32; DEP_COLS]) {
         self.num_allocated_rows += 1;
-        let idx = self.contents.len();
-        self.contents.push((*determinant, *dependent));
+        let idx = if let Some(RowId(idx)) = self.free_rows.as_mut().and_then(Vec::pop) {
+            self.num_free_rows -= 1;
+            self.contents[idx as usize] = (*determinant, *dependent);
+            idx as usize
+        } else {
+            self.contents.push((*determinant, *dependent));
+            self.contents.len() - 1
+        };
+        self.occupied.insert(idx);
+        self.index_row(idx);
+        self.give_stable_ids(RowId(idx as u32), carried);
         let row = unsafe { self.contents.static_ref(idx) };
         self.determine_map
             .insert(&row.0, (RowId(idx as u32), &row.1));
-        &row.1
+        (RowId(idx as u32), &row.1)
     }
 
     pub fn first_row(&self) -> Option<RowId> {
-        for idx in 0..self.contents.len() {
-            if self.contents[idx] != ([EMPTY; DET_COLS], [EMPTY; DEP_COLS]) {
-                return Some(RowId(idx as u32));
-            }
-        }
-        None
+        self.occupied.iter().next().map(|idx| RowId(idx as u32))
     }
 
     pub fn next_row(&self, row: RowId) -> Option<RowId> {
         for idx in (row.0 as usize + 1)..self.contents.len() {
-            if self.contents[idx] != ([EMPTY; DET_COLS], [EMPTY; DEP_COLS]) {
+            if self.occupied.contains(idx) {
                 return Some(RowId(idx as u32));
             }
         }
@@ -87,31 +407,164 @@ impl<const DET_COLS: usize, const DEP_COLS: usize> Table<DET_COLS, DEP_COLS> {
         self.contents[row.0 as usize]
     }
 
+    #[doc(hidden)]
+    pub fn raw_slots(&self) -> &[([u32; DET_COLS], [u32; DEP_COLS])] {
+        self.contents.as_ref()
+    }
+
     pub fn delete_row(&mut self, row: RowId) -> bool {
-        if self
-            .determine_map
-            .remove(&self.contents[row.0 as usize].0)
-            .is_some()
-        {
+        assert!(!self.finalized, "deleted from finalized table");
+        if self.occupied.contains(row.0 as usize) {
+            self.unindex_row(row.0 as usize);
+            self.determine_map.remove(&self.contents[row.0 as usize].0);
             self.num_allocated_rows -= 1;
             self.num_free_rows += 1;
+            self.occupied.remove(row.0 as usize);
             self.contents[row.0 as usize] = ([EMPTY; DET_COLS], [EMPTY; DEP_COLS]);
+            if let Some(free_rows) = &mut self.free_rows {
+                free_rows.push(row);
+            }
             true
         } else {
             false
         }
     }
 
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: Fn(&[u32; DET_COLS], &[u32; DEP_COLS]) -> bool,
+    {
+        assert!(!self.finalized, "deleted from finalized table");
+        let rejected: Vec<RowId> = (0..self.contents.len())
+            .filter(|idx| {
+                let row = &self.contents[*idx];
+                self.occupied.contains(*idx) && !f(&row.0, &row.1)
+            })
+            .map(|idx| RowId(idx as u32))
+            .collect();
+        for row in rejected {
+            self.delete_row(row);
+        }
+    }
+
+    /// Removes every row while keeping the table's symbol, merge policy, column signedness and
+    /// indices, so the table behaves as if it were freshly constructed with the same options.
+    pub fn clear(&mut self) {
+        assert!(!self.finalized, "deleted from finalized table");
+        // `determine_map` borrows from `contents`, so it has to go first.
+        self.determine_map.clear();
+        self.contents = VirtualVec::new();
+        self.occupied.clear();
+        self.num_allocated_rows = 0;
+        self.num_free_rows = 0;
+        if let Some(stats) = &mut self.cons_stats {
+            *stats = (0, 0);
+        }
+        if let Some(index) = &mut self.reverse_index {
+            index.clear();
+        }
+        if let Some(free_rows) = &mut self.free_rows {
+            free_rows.clear();
+        }
+        if let Some((_, index)) = &mut self.dep_index {
+            index.clear();
+        }
+        if let Some(stable) = &mut self.stable_ids {
+            stable.rows.clear();
+            stable.ids.clear();
+        }
+    }
+
     pub fn map(&self, det: &[u32; DET_COLS]) -> Option<&[u32; DEP_COLS]> {
-        self.determine_map.get(det).map(|(_, dep)| *dep)
+        if self.finalized {
+            self.contents
+                .as_ref()
+                .iter()
+                .enumerate()
+                .find(|(idx, row)| row.0 == *det && self.occupied.contains(*idx))
+                .map(|(_, row)| &row.1)
+        } else {
+            self.determine_map.get(det).map(|(_, dep)| *dep)
+        }
+    }
+
+    pub fn contains_determinant(&self, det: &[u32; DET_COLS]) -> bool {
+        self.map(det).is_some()
+    }
+
+    pub fn count(&self) -> usize {
+        self.num_allocated_rows as usize
+    }
+
+    pub fn rows_eq(&self, other: &Table<DET_COLS, DEP_COLS>) -> bool {
+        if self.count() != other.count() {
+            return false;
+        }
+        let mut lhs: Vec<_> = self.iter().collect();
+        let mut rhs: Vec<_> = other.iter().collect();
+        lhs.sort_unstable();
+        rhs.sort_unstable();
+        lhs == rhs
+    }
+
+    pub fn finalize(&mut self) {
+        self.determine_map = HashMap::new();
+        self.finalized = true;
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.finalized
     }
 
     pub fn iter(&self) -> impl Iterator<Item = ([u32; DET_COLS], [u32; DEP_COLS])> + '_ {
         self.contents
             .as_ref()
-            .into_iter()
-            .filter(|row| *row != &([EMPTY; DET_COLS], [EMPTY; DEP_COLS]))
-            .map(|row| *row)
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.occupied.contains(*idx))
+            .map(|(_, row)| *row)
+    }
+
+    pub fn serialize(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.num_allocated_rows.to_le_bytes());
+        for (det, dep) in self.iter() {
+            for col in det.iter().chain(dep.iter()) {
+                out.extend_from_slice(&col.to_le_bytes());
+            }
+        }
+    }
+
+    pub fn deserialize(bytes: &[u8], symbol: IdentifierId) -> Option<(Self, &[u8])> {
+        fn read_u32(bytes: &mut &[u8]) -> Option<u32> {
+            let (word, rest) = bytes.split_first_chunk::<4>()?;
+            *bytes = rest;
+            Some(u32::from_le_bytes(*word))
+        }
+
+        let mut bytes = bytes;
+        let num_rows = read_u32(&mut bytes)?;
+        let mut table = Self::new(symbol);
+        for _ in 0..num_rows {
+            let mut det = [0; DET_COLS];
+            let mut dep = [0; DEP_COLS];
+            for col in det.iter_mut().chain(dep.iter_mut()) {
+                *col = read_u32(&mut bytes)?;
+            }
+            if table.determine_map.contains_key(&det) {
+                return None;
+            }
+            table.push_row(&det, &dep);
+        }
+        Some((table, bytes))
+    }
+
+    /// Formats a determinant the way `dump` formats a row's.
+    pub fn dump_determinant(&self, det: &[u32; DET_COLS], interner: &StringInterner) -> String {
+        format!(
+            "{}({})",
+            interner.get(self.symbol),
+            dump_cols(det, &self.signed_cols.0)
+        )
     }
 
     pub fn dump(&self, interner: &StringInterner) -> String {
@@ -120,13 +573,76 @@ impl<const DET_COLS: usize, const DEP_COLS: usize> Table<DET_COLS, DEP_COLS> {
         let mut maybe_row_id = self.first_row();
         while let Some(row_id) = maybe_row_id {
             let row = self.get_row(row_id);
-            s = format!("{}{}({:?}) -> {:?}\n", s, symbol, row.0, row.1);
+            s = format!(
+                "{}{}({}) -> {}\n",
+                s,
+                symbol,
+                dump_cols(&row.0, &self.signed_cols.0),
+                dump_cols(&row.1, &self.signed_cols.1)
+            );
             maybe_row_id = self.next_row(row_id);
         }
         s
     }
 }
 
+fn dep_key(signed: bool, value: u32) -> i64 {
+    if signed {
+        i32::from_ne_bytes(value.to_ne_bytes()).into()
+    } else {
+        value.into()
+    }
+}
+
+fn dump_cols(cols: &[u32], signed: &[bool]) -> String {
+    let cols: Vec<String> = cols
+        .iter()
+        .zip(signed)
+        .map(|(col, signed)| {
+            if *signed {
+                i32::from_ne_bytes(col.to_ne_bytes()).to_string()
+            } else {
+                col.to_string()
+            }
+        })
+        .collect();
+    format!("[{}]", cols.join(", "))
+}
+
+impl<const DET_COLS: usize, const DEP_COLS: usize> Clone for Table<DET_COLS, DEP_COLS> {
+    fn clone(&self) -> Self {
+        let mut table = Self {
+            contents: VirtualVec::new(),
+            occupied: self.occupied.clone(),
+            determine_map: HashMap::with_capacity(self.determine_map.len()),
+
+            symbol: self.symbol,
+
+            num_allocated_rows: self.num_allocated_rows,
+            num_free_rows: self.num_free_rows,
+
+            finalized: self.finalized,
+            cons_stats: self.cons_stats,
+            merge_policy: self.merge_policy,
+            reverse_index: self.reverse_index.clone(),
+            free_rows: self.free_rows.clone(),
+            dep_index: self.dep_index.clone(),
+            signed_cols: self.signed_cols,
+            stable_ids: self.stable_ids.clone(),
+        };
+        for (idx, row) in self.contents.as_ref().iter().enumerate() {
+            table.contents.push(*row);
+            if !table.finalized && table.occupied.contains(idx) {
+                let row = unsafe { table.contents.static_ref(idx) };
+                table
+                    .determine_map
+                    .insert(&row.0, (RowId(idx as u32), &row.1));
+            }
+        }
+        table
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,7 +651,6 @@ mod tests {
     use util::interner::StringInterner;
 
     #[test]
-    #[cfg_attr(miri, ignore)]
     fn simple_table() {
         let mut buf: [u64; 1] = [0; 1];
         let arena = Arena::new_backed(&mut buf);
@@ -167,4 +682,468 @@ mod tests {
         assert_eq!(table.num_allocated_rows, 3);
         assert_eq!(table.num_free_rows, 6);
     }
+
+    #[test]
+    fn finalize_table() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<2, 1>::new(interner.intern("blah"));
+        for idx in 0..100 {
+            table.insert_row(&[idx, idx + 1], &[idx + 2], |_, _| unreachable!());
+        }
+        assert!(table.determine_map.capacity() >= 100);
+        table.finalize();
+        assert!(table.is_finalized());
+        assert_eq!(table.determine_map.capacity(), 0);
+        assert_eq!(table.iter().count(), 100);
+        assert_eq!(table.map(&[5, 6]), Some(&[7]));
+        assert_eq!(table.map(&[6, 5]), None);
+        let first_id = table.first_row().unwrap();
+        assert_eq!(table.get_row(first_id), ([0, 1], [2]));
+        assert!(table.dump(&interner).starts_with("blah([0, 1]) -> [2]\n"));
+    }
+
+    #[test]
+    fn contains_and_count() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<2, 1>::new(interner.intern("blah"));
+        assert_eq!(table.count(), 0);
+        assert!(!table.contains_determinant(&[0, 1]));
+        table.insert_row(&[0, 1], &[2], |_, _| unreachable!());
+        table.insert_row(&[1, 2], &[3], |_, _| unreachable!());
+        table.insert_row(&[0, 1], &[4], |_, old| *old);
+        assert_eq!(table.count(), 2);
+        assert!(table.contains_determinant(&[0, 1]));
+        assert!(table.contains_determinant(&[1, 2]));
+        assert!(!table.contains_determinant(&[2, 1]));
+        let first_id = table.first_row().unwrap();
+        let (det, _) = table.get_row(first_id);
+        assert!(table.delete_row(first_id));
+        assert_eq!(table.count(), 1);
+        assert!(!table.contains_determinant(&det));
+        table.retain(|_, _| false);
+        assert_eq!(table.count(), 0);
+        assert!(!table.contains_determinant(&[0, 1]));
+        assert!(!table.contains_determinant(&[1, 2]));
+    }
+
+    #[test]
+    fn rows_eq() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut lhs = Table::<2, 1>::new(interner.intern("blah"));
+        let mut rhs = Table::<2, 1>::new(interner.intern("blah"));
+        assert!(lhs.rows_eq(&rhs));
+        lhs.insert_row(&[0, 1], &[2], |_, _| unreachable!());
+        lhs.insert_row(&[1, 2], &[3], |_, _| unreachable!());
+        lhs.insert_row(&[2, 3], &[4], |_, _| unreachable!());
+        rhs.insert_row(&[5, 5], &[5], |_, _| unreachable!());
+        rhs.insert_row(&[2, 3], &[4], |_, _| unreachable!());
+        rhs.insert_row(&[0, 1], &[2], |_, _| unreachable!());
+        assert!(!lhs.rows_eq(&rhs));
+        rhs.insert_row(&[1, 2], &[3], |_, _| unreachable!());
+        rhs.retain(|det, _| det != &[5, 5]);
+        assert!(lhs.rows_eq(&rhs));
+        assert!(rhs.rows_eq(&lhs));
+        assert_ne!(lhs.dump(&interner), rhs.dump(&interner));
+        rhs.insert_row(&[1, 2], &[7], |new, _| *new);
+        assert!(!lhs.rows_eq(&rhs));
+    }
+
+    #[test]
+    fn merge_into() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let plus = interner.intern("+");
+        let mut lhs = Table::<2, 1>::new(plus);
+        let mut rhs = Table::<2, 1>::new(plus);
+        lhs.insert_row(&[0, 1], &[2], |_, _| unreachable!());
+        lhs.insert_row(&[1, 1], &[3], |_, _| unreachable!());
+        rhs.insert_row(&[0, 1], &[4], |_, _| unreachable!());
+        rhs.insert_row(&[2, 2], &[5], |_, _| unreachable!());
+        let mut merged = vec![];
+        lhs.merge_into(&rhs, |new, old| {
+            merged.push((*new, *old));
+            [new[0].min(old[0])]
+        });
+        assert_eq!(merged, vec![([4], [2])]);
+        assert_eq!(lhs.map(&[0, 1]), Some(&[2]));
+        assert_eq!(lhs.map(&[1, 1]), Some(&[3]));
+        assert_eq!(lhs.map(&[2, 2]), Some(&[5]));
+        assert_eq!(lhs.iter().count(), 3);
+        assert_eq!(rhs.iter().count(), 2);
+    }
+
+    #[test]
+    fn range_by_dep() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<1, 2>::new_with_dep_index(interner.intern("interval"), 0);
+        table.insert_row(&[0], &[0, 5], |_, _| unreachable!());
+        table.insert_row(&[1], &[3, 8], |_, _| unreachable!());
+        table.insert_row(&[2], &[10, 12], |_, _| unreachable!());
+        table.insert_row(&[3], &[1, 2], |_, _| unreachable!());
+        let overlapping = |table: &Table<1, 2>, low: u32, high: u32| {
+            table
+                .range_by_dep(0, 0..i64::from(high) + 1)
+                .map(|row| table.get_row(row))
+                .filter(|(_, dep)| dep[1] >= low)
+                .map(|(det, _)| det[0])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(overlapping(&table, 4, 9), vec![0, 1]);
+        assert_eq!(overlapping(&table, 9, 20), vec![2]);
+        assert_eq!(overlapping(&table, 0, 1), vec![0, 3]);
+
+        table.insert_row(&[1], &[4, 6], |new, _| *new);
+        assert_eq!(table.range_by_dep(0, 3..4).count(), 0);
+        assert_eq!(overlapping(&table, 4, 9), vec![0, 1]);
+        let first_id = table.first_row().unwrap();
+        assert!(table.delete_row(first_id));
+        assert_eq!(overlapping(&table, 4, 9), vec![1]);
+        assert_eq!(table.clone().range_by_dep(0, 0..20).count(), 3);
+
+        let mut table = Table::<1, 2>::new_with_signed_dep_index(
+            interner.intern("interval"),
+            [false],
+            [true, true],
+            0,
+        );
+        for (class, (low, high)) in [(-10, -5), (-3, 4), (2, 7), (i32::MIN, i32::MAX)]
+            .into_iter()
+            .enumerate()
+        {
+            table.insert_row(
+                &[class as u32],
+                &[low as u32, high as u32],
+                |_, _| unreachable!(),
+            );
+        }
+        let overlapping = |table: &Table<1, 2>, low: i32, high: i32| {
+            table
+                .range_by_dep(0, i64::from(i32::MIN)..i64::from(high) + 1)
+                .map(|row| table.get_row(row))
+                .filter(|(_, dep)| dep[1] as i32 >= low)
+                .map(|(det, _)| det[0])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(overlapping(&table, -4, 0), vec![3, 1]);
+        assert_eq!(overlapping(&table, -7, -6), vec![3, 0]);
+        assert_eq!(overlapping(&table, 5, 9), vec![3, 2]);
+        assert_eq!(
+            table.range_by_dep(0, -10..0).collect::<Vec<_>>(),
+            table.range_by_dep(0, -10..-2).collect::<Vec<_>>()
+        );
+        assert_eq!(table.range_by_dep(0, -10..0).count(), 2);
+        assert_eq!(table.range_by_dep(0, 0..i64::from(u32::MAX)).count(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_finalized_table() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<2, 1>::new(interner.intern("blah"));
+        table.finalize();
+        table.insert_row(&[0, 1], &[2], |_, _| unreachable!());
+    }
+
+    #[test]
+    fn retain_table() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<2, 1>::new(interner.intern("blah"));
+        for idx in 0..100 {
+            table.insert_row(&[idx, idx + 1], &[idx % 3], |_, _| unreachable!());
+        }
+        table.retain(|_, dep| dep[0] != 1);
+        assert_eq!(table.num_allocated_rows, 67);
+        assert_eq!(table.num_free_rows, 33);
+        assert_eq!(table.iter().count(), 67);
+        assert!(table.iter().all(|(_, dep)| dep[0] != 1));
+        assert_eq!(table.map(&[0, 1]), Some(&[0]));
+        assert_eq!(table.map(&[1, 2]), None);
+        assert_eq!(table.insert_row(&[1, 2], &[4], |_, _| unreachable!()), &[4]);
+        assert_eq!(table.insert_row(&[2, 3], &[5], |_, x| *x), &[2]);
+        assert_eq!(table.num_allocated_rows, 68);
+    }
+
+    #[test]
+    fn clear_table() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table =
+            Table::<1, 1>::new_with_signed_dep_index(interner.intern("cons"), [false], [true], 0);
+        for idx in 0..10 {
+            table.insert_row(&[idx], &[(idx as i32 - 5) as u32], |_, _| unreachable!());
+        }
+        table.clear();
+        assert_eq!(table.count(), 0);
+        assert_eq!(table.num_free_rows, 0);
+        assert_eq!(table.iter().count(), 0);
+        assert_eq!(table.map(&[0]), None);
+        assert_eq!(table.range_by_dep(0, -10..10).count(), 0);
+
+        table.insert_row(&[3], &[-2i32 as u32], |_, _| unreachable!());
+        table.insert_row(&[4], &[2], |_, _| unreachable!());
+        assert_eq!(table.count(), 2);
+        assert_eq!(table.map(&[3]), Some(&[-2i32 as u32]));
+        assert_eq!(table.range_by_dep(0, -5..0).count(), 1);
+    }
+
+    #[test]
+    fn serialize_table() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let symbol = interner.intern("blah");
+        let mut table = Table::<2, 1>::new(symbol);
+        for idx in 0..10 {
+            table.insert_row(&[idx, idx + 1], &[idx + 2], |_, _| unreachable!());
+        }
+        let first_id = table.first_row().unwrap();
+        table.delete_row(first_id);
+
+        let mut bytes = vec![];
+        table.serialize(&mut bytes);
+        assert_eq!(bytes.len(), 4 + 9 * 3 * 4);
+        assert_eq!(&bytes[0..4], &9u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        let (loaded, rest) = Table::<2, 1>::deserialize(&bytes, symbol).unwrap();
+        assert_eq!(rest, &[1, 2, 3]);
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            table.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.num_allocated_rows, 9);
+        assert_eq!(loaded.num_free_rows, 0);
+        assert_eq!(loaded.map(&[5, 6]), Some(&[7]));
+        assert_eq!(loaded.map(&[0, 1]), None);
+        assert_eq!(loaded.dump(&interner), table.dump(&interner));
+    }
+
+    #[test]
+    fn deserialize_truncated_table() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let symbol = interner.intern("blah");
+        let mut table = Table::<2, 1>::new(symbol);
+        table.insert_row(&[0, 1], &[2], |_, _| unreachable!());
+        table.insert_row(&[1, 2], &[3], |_, _| unreachable!());
+
+        let mut bytes = vec![];
+        table.serialize(&mut bytes);
+        for len in 0..bytes.len() {
+            assert!(Table::<2, 1>::deserialize(&bytes[..len], symbol).is_none());
+        }
+        assert!(Table::<2, 1>::deserialize(&bytes, symbol).is_some());
+
+        let mut duplicate = vec![];
+        duplicate.extend_from_slice(&2u32.to_le_bytes());
+        for col in [0u32, 1, 2, 0, 1, 3] {
+            duplicate.extend_from_slice(&col.to_le_bytes());
+        }
+        assert!(Table::<2, 1>::deserialize(&duplicate, symbol).is_none());
+    }
+
+    #[test]
+    fn try_insert_new() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut fast = Table::<2, 1>::new(interner.intern("fast"));
+        let mut slow = Table::<2, 1>::new(interner.intern("slow"));
+        for idx in 0..10000 {
+            let det = [idx % 100, idx % 7];
+            let dep = [idx];
+            let expected = *slow.insert_row(&det, &dep, |_, old| *old);
+            match fast.try_insert_new(&det, &dep) {
+                Ok(()) => assert_eq!(expected, dep),
+                Err(existing) => {
+                    assert_eq!(expected, existing);
+                    assert!(existing[0] < idx);
+                }
+            }
+        }
+        assert_eq!(fast.num_allocated_rows, slow.num_allocated_rows);
+        assert_eq!(fast.num_allocated_rows, 700);
+        assert_eq!(fast.num_free_rows, 0);
+        for idx in 0..100 {
+            for jdx in 0..7 {
+                assert_eq!(fast.map(&[idx, jdx]), slow.map(&[idx, jdx]));
+            }
+        }
+    }
+
+    #[test]
+    fn try_insert_new_row() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<2, 1>::new_with_free_list(interner.intern("blah"));
+        let first = table.try_insert_new_row(&[0, 1], &[2]).unwrap();
+        let second = table.try_insert_new_row(&[1, 1], &[3]).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(table.get_row(first), ([0, 1], [2]));
+        assert_eq!(table.try_insert_new_row(&[0, 1], &[4]), Err((first, [2])));
+
+        table.delete_row(first);
+        let reused = table.try_insert_new_row(&[2, 1], &[5]).unwrap();
+        assert_eq!(reused, first);
+        assert_eq!(table.get_row(reused), ([2, 1], [5]));
+        assert_eq!(table.try_insert_new_row(&[1, 1], &[6]), Err((second, [3])));
+    }
+
+    #[test]
+    fn cons_stats() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<2, 1>::new_with_cons_stats(interner.intern("blah"));
+        for idx in 0..10 {
+            table.insert_row(&[idx % 4, 0], &[idx], |_, x| *x);
+        }
+        assert_eq!(table.cons_stats(), (6, 4));
+        assert!(table.try_insert_new(&[0, 0], &[0]).is_err());
+        assert!(table.try_insert_new(&[0, 1], &[0]).is_ok());
+        assert_eq!(table.cons_stats(), (7, 5));
+        assert_eq!(table.clone().cons_stats(), (7, 5));
+
+        let mut table = Table::<2, 1>::new(interner.intern("blah"));
+        table.insert_row(&[0, 0], &[0], |_, _| unreachable!());
+        table.insert_row(&[0, 0], &[1], |_, x| *x);
+        assert_eq!(table.cons_stats(), (0, 0));
+    }
+
+    #[test]
+    fn sentinel_row() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<1, 1>::new(interner.intern("cons"));
+        assert_eq!(
+            table.insert_row(&[EMPTY], &[EMPTY], |_, _| unreachable!()),
+            &[EMPTY]
+        );
+        let row_id = table.first_row().unwrap();
+        assert_eq!(table.get_row(row_id), ([EMPTY], [EMPTY]));
+        assert_eq!(table.next_row(row_id), None);
+        assert_eq!(table.iter().collect::<Vec<_>>(), vec![([EMPTY], [EMPTY])]);
+        assert_eq!(table.map(&[EMPTY]), Some(&[EMPTY]));
+        assert_eq!(table.insert_row(&[EMPTY], &[0], |_, x| *x), &[EMPTY]);
+        let row_id = table.first_row().unwrap();
+        assert!(table.delete_row(row_id));
+        assert!(!table.delete_row(row_id));
+        assert_eq!(table.first_row(), None);
+        assert_eq!(table.iter().count(), 0);
+        assert_eq!(table.num_allocated_rows, 0);
+    }
+
+    #[test]
+    fn raw_slots() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<1, 1>::new(interner.intern("cons"));
+        for idx in 0..4 {
+            table.insert_row(&[idx], &[idx + 10], |_, _| unreachable!());
+        }
+        let second = table.next_row(table.first_row().unwrap()).unwrap();
+        assert!(table.delete_row(second));
+        assert_eq!(
+            table.raw_slots(),
+            &[([0], [10]), ([EMPTY], [EMPTY]), ([2], [12]), ([3], [13])]
+        );
+        table.insert_row(&[1], &[11], |_, _| unreachable!());
+        assert_eq!(table.raw_slots().len(), 5);
+        assert_eq!(table.raw_slots()[1], ([EMPTY], [EMPTY]));
+        assert_eq!(table.raw_slots()[4], ([1], [11]));
+    }
+
+    #[test]
+    fn reverse_index() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<2, 1>::new_with_reverse_index(interner.intern("+"));
+        let c = ClassId::new;
+        table.insert_row(&[0, 1], &[2], |_, _| unreachable!());
+        table.insert_row(&[1, 1], &[3], |_, _| unreachable!());
+        table.insert_row(&[3, 0], &[4], |_, _| unreachable!());
+        let first = table.first_row().unwrap();
+        let second = table.next_row(first).unwrap();
+        let third = table.next_row(second).unwrap();
+        assert_eq!(table.rows_referencing(c(0)), &[first, third]);
+        assert_eq!(table.rows_referencing(c(1)), &[first, second]);
+        assert_eq!(table.rows_referencing(c(3)), &[second, third]);
+        assert_eq!(table.rows_referencing(c(2)), &[first]);
+        assert_eq!(table.rows_referencing(c(5)), &[]);
+
+        assert!(table.delete_row(second));
+        assert_eq!(table.rows_referencing(c(1)), &[first]);
+        assert_eq!(table.rows_referencing(c(3)), &[third]);
+        table.retain(|det, _| det[0] != 0);
+        assert_eq!(table.rows_referencing(c(0)), &[third]);
+        assert_eq!(table.rows_referencing(c(1)), &[]);
+        assert_eq!(table.clone().rows_referencing(c(4)), &[third]);
+
+        table.insert_row(&[3, 0], &[5], |_, old| *old);
+        let moved = table.next_row(third).unwrap();
+        assert_eq!(table.rows_referencing(c(0)), &[moved]);
+        assert_eq!(table.rows_referencing(c(4)), &[moved]);
+
+        let mut table = Table::<2, 1>::new(interner.intern("+"));
+        table.insert_row(&[0, 1], &[2], |_, _| unreachable!());
+        assert_eq!(table.rows_referencing(c(0)), &[]);
+    }
+
+    #[test]
+    fn free_list() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut table = Table::<1, 1>::new_with_free_list(interner.intern("cons"));
+        for idx in 0..4 {
+            table.insert_row(&[idx], &[idx + 10], |_, _| unreachable!());
+        }
+        let second = table.next_row(table.first_row().unwrap()).unwrap();
+        assert!(table.delete_row(second));
+        assert_eq!(table.num_free_rows, 1);
+        table.insert_row(&[7], &[17], |_, _| unreachable!());
+        assert_eq!(table.raw_slots().len(), 4);
+        assert_eq!(table.num_free_rows, 0);
+        assert_eq!(table.get_row(second), ([7], [17]));
+        assert_eq!(table.map(&[7]), Some(&[17]));
+        assert_eq!(table.map(&[1]), None);
+
+        for _ in 0..10 {
+            table.insert_row(&[3], &[20], |new, _| *new);
+        }
+        assert_eq!(table.raw_slots().len(), 4);
+        assert_eq!(table.map(&[3]), Some(&[20]));
+        table.retain(|det, _| det[0] != 0);
+        table.insert_row(&[8], &[18], |_, _| unreachable!());
+        table.insert_row(&[9], &[19], |_, _| unreachable!());
+        assert_eq!(table.raw_slots().len(), 5);
+        assert_eq!(table.iter().count(), 5);
+        assert_eq!(table.num_allocated_rows, 5);
+        assert_eq!(table.clone().iter().count(), 5);
+
+        let mut table = Table::<1, 1>::new(interner.intern("cons"));
+        table.insert_row(&[0], &[0], |_, _| unreachable!());
+        table.delete_row(table.first_row().unwrap());
+        table.insert_row(&[0], &[0], |_, _| unreachable!());
+        assert_eq!(table.raw_slots().len(), 2);
+    }
 }
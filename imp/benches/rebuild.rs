@@ -0,0 +1,64 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use imp::bench_support::{build_chain, build_tree};
+use imp::rewrite::Rewrite;
+use imp::ssa::Term;
+use util::arena::Arena;
+use util::interner::StringInterner;
+
+const FOLD_ADD: Rewrite = Rewrite {
+    name: "fold-add",
+    rule: |graph, term| {
+        let Term::Add { lhs, rhs, .. } = term else {
+            return None;
+        };
+        let lhs = graph.constant_value(lhs)?;
+        let rhs = graph.constant_value(rhs)?;
+        Some(graph.constant(lhs.wrapping_add(rhs)))
+    },
+};
+
+fn rebuild(c: &mut Criterion) {
+    let mut buf: [u64; 100] = [0; 100];
+    let arena = Arena::new_backed(&mut buf);
+    let mut interner = StringInterner::new(&arena);
+
+    let mut group = c.benchmark_group("rebuild");
+    for n in [16, 64, 256] {
+        let (graph, _) = build_chain(&mut interner, n);
+        group.bench_with_input(BenchmarkId::new("chain", n), &graph, |b, graph| {
+            b.iter(|| graph.clone().rebuild())
+        });
+    }
+    for depth in [4, 6, 8] {
+        let (graph, _) = build_tree(&mut interner, depth);
+        group.bench_with_input(BenchmarkId::new("tree", depth), &graph, |b, graph| {
+            b.iter(|| graph.clone().rebuild())
+        });
+    }
+    group.finish();
+}
+
+fn saturate(c: &mut Criterion) {
+    let mut buf: [u64; 100] = [0; 100];
+    let arena = Arena::new_backed(&mut buf);
+    let mut interner = StringInterner::new(&arena);
+
+    let mut group = c.benchmark_group("saturate");
+    for n in [8, 32] {
+        let (graph, _) = build_chain(&mut interner, n);
+        group.bench_with_input(BenchmarkId::new("chain", n), &graph, |b, graph| {
+            b.iter(|| graph.clone().saturate(&[FOLD_ADD], 100))
+        });
+    }
+    for depth in [3, 5] {
+        let (graph, _) = build_tree(&mut interner, depth);
+        group.bench_with_input(BenchmarkId::new("tree", depth), &graph, |b, graph| {
+            b.iter(|| graph.clone().saturate(&[FOLD_ADD], 100))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rebuild, saturate);
+criterion_main!(benches);
@@ -1,91 +1,427 @@
 use core::cell::RefCell;
-use std::collections::HashMap;
+use core::cmp::{max, min};
+use core::ops::{Deref, DerefMut};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
+use util::arena::Arena;
 use util::interner::{IdentifierId, StringInterner};
 
-use crate::ast::{BlockAST, ExpressionAST, ProgramAST, StatementAST};
-use crate::interval::IntervalDomain;
-use crate::ssa::{Graph, SSADomain, Term};
+use crate::ast::{BlockAST, ExpressionAST, FunctionAST, ProgramAST, StatementAST};
+use crate::ssa::{Graph, OpaqueSites, SSADomain, Term};
+use crate::visit::{Visitor, walk_expr};
+
+pub const DEFAULT_MAX_EXPR_DEPTH: usize = 256;
 
 pub trait AbstractDomain: Clone + PartialEq {
     type Value;
 
     fn interp_expr(&self, expr: &ExpressionAST<'_>) -> Self::Value;
+    fn opaque_expr(&self, expr: &ExpressionAST<'_>) -> Self::Value;
     fn get(&self, iden: IdentifierId) -> Self::Value;
     fn assign(&mut self, iden: IdentifierId, val: Self::Value);
-    fn branch(&self, cond: Self::Value) -> (Self, Self);
+    fn branch(&self, cond: &ExpressionAST<'_>, value: Self::Value) -> (Self, Self);
     fn finish_with(&mut self, val: Self::Value);
     fn join(&self, other: &Self) -> Self;
-    fn widen(&self, other: &Self) -> (Self, bool);
+    fn widen(&self, back_edge: &Self) -> (Self, bool);
+
+    fn narrow(&self, _back_edge: &Self) -> Option<Self> {
+        None
+    }
+
+    fn ascends_from(&self, _previous: &Self) -> Result<(), IdentifierId> {
+        Ok(())
+    }
+
+    fn max_expr_depth(&self) -> usize {
+        DEFAULT_MAX_EXPR_DEPTH
+    }
+}
+
+pub struct ProgramGraphs {
+    names: Vec<IdentifierId>,
+    graphs: Vec<Graph>,
+}
+
+impl ProgramGraphs {
+    pub fn names(&self) -> &[IdentifierId] {
+        &self.names
+    }
+
+    pub fn iter_named(&self) -> impl Iterator<Item = (IdentifierId, &Graph)> + '_ {
+        self.names.iter().copied().zip(&self.graphs)
+    }
+
+    pub fn into_graphs(self) -> Vec<Graph> {
+        self.graphs
+    }
+
+    pub fn dump_all(&self, interner: &StringInterner) -> String {
+        let mut s = String::new();
+        for (name, graph) in self.iter_named() {
+            s = format!("{}{}:\n{}", s, interner.get(name), graph.dump(interner));
+        }
+        s
+    }
+}
+
+impl Deref for ProgramGraphs {
+    type Target = [Graph];
+
+    fn deref(&self) -> &[Graph] {
+        &self.graphs
+    }
+}
+
+impl DerefMut for ProgramGraphs {
+    fn deref_mut(&mut self) -> &mut [Graph] {
+        &mut self.graphs
+    }
+}
+
+pub fn abstract_interpret(
+    program: &ProgramAST<'_>,
+    interner: &mut StringInterner,
+) -> Result<ProgramGraphs, IdentifierId> {
+    // The scratch arena only holds one function's parameters at a time, since each function is
+    // lowered inside its own scope, so a small buffer on the stack is enough. Programs with more
+    // than 1024 parameters in one function need `abstract_interpret_in` with a larger arena.
+    let mut buf: [u64; 1024] = [0; 1024];
+    let mut scratch = Arena::new_backed(&mut buf);
+    abstract_interpret_in(program, interner, &mut scratch)
 }
 
-pub fn abstract_interpret(program: &ProgramAST<'_>, interner: &mut StringInterner) -> Vec<Graph> {
+pub fn abstract_interpret_in(
+    program: &ProgramAST<'_>,
+    interner: &mut StringInterner,
+    scratch: &mut Arena<'_>,
+) -> Result<ProgramGraphs, IdentifierId> {
+    let mut names = vec![];
     let mut graphs = vec![];
-    for func in program.funcs.as_ref() {
-        let mut graph = Graph::new(interner);
-        let start = graph.makeset();
-        graph.insert(Term::Start { root: start });
-        let mut params = vec![];
-        let mut param_idens = vec![];
-        for (idx, iden) in func.params.as_ref().into_iter().enumerate() {
-            let root = graph.makeset();
-            graph.insert(Term::Param {
-                start,
-                index: idx as u32,
-                root,
-            });
-            params.push((*iden, graph.find(root)));
-            param_idens.push(*iden);
-        }
-
-        let interval = IntervalDomain::new(param_idens);
-        println!("{:?}", ai_block(&func.block, &interval));
-
-        let graph = RefCell::new(graph);
-        let static_phis = RefCell::new(HashMap::new());
-        let domain = SSADomain::new(&graph, &static_phis, start, params);
-        ai_block(&func.block, &domain);
-        graphs.push(graph.into_inner());
+    for (func_idx, func) in program.funcs.as_ref().iter().enumerate() {
+        let sites = OpaqueSites::new(func_idx as u32, func);
+        let graph = scratch.scope(|scratch| {
+            let mut graph = Graph::new(interner);
+            let start = graph.makeset();
+            graph.insert(Term::Start { root: start });
+            let params = scratch.collect_exact(func.params.as_ref().iter().enumerate().map(
+                |(idx, iden)| {
+                    let root = graph.makeset();
+                    graph.insert(Term::Param {
+                        start,
+                        index: idx as u32,
+                        root,
+                    });
+                    (*iden, graph.find(root))
+                },
+            ));
+
+            let graph = RefCell::new(graph);
+            let static_phis = RefCell::new(HashMap::new());
+            let domain =
+                SSADomain::new(&graph, &sites, &static_phis, start, params.iter().copied());
+            let mut domain = ai_block(&func.block, &domain)?;
+            if !domain.is_finished() {
+                let value = graph.borrow_mut().opaque(sites.implicit_return());
+                domain.finish_with(value);
+            }
+            Ok(graph.into_inner())
+        })?;
+        names.push(func.name);
+        graphs.push(graph);
     }
+    Ok(ProgramGraphs { names, graphs })
+}
+
+pub fn all_terms(graphs: &[Graph]) -> impl Iterator<Item = (usize, Term)> + '_ {
     graphs
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, graph)| graph.terms().map(move |term| (idx, term)))
+}
+
+struct CallCollector {
+    callees: BTreeSet<IdentifierId>,
+}
+
+impl Visitor for CallCollector {
+    fn visit_expr(&mut self, expr: &ExpressionAST<'_>) {
+        if let ExpressionAST::Call(callee, _) = expr {
+            self.callees.insert(*callee);
+        }
+        walk_expr(expr, self);
+    }
+}
+
+pub fn call_graph(program: &ProgramAST<'_>) -> Vec<BTreeSet<usize>> {
+    let funcs = program.funcs.as_ref();
+    let indices: BTreeMap<IdentifierId, usize> = funcs
+        .iter()
+        .enumerate()
+        .map(|(idx, func)| (func.name, idx))
+        .collect();
+    funcs
+        .iter()
+        .map(|func| {
+            let mut collector = CallCollector {
+                callees: BTreeSet::new(),
+            };
+            collector.visit_func(func);
+            collector
+                .callees
+                .into_iter()
+                .filter_map(|callee| indices.get(&callee).copied())
+                .collect()
+        })
+        .collect()
+}
+
+pub fn call_sccs(graph: &[BTreeSet<usize>]) -> Vec<Vec<usize>> {
+    struct Tarjan<'a> {
+        graph: &'a [BTreeSet<usize>],
+        index: Vec<Option<usize>>,
+        lowlink: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next_index: usize,
+        sccs: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, node: usize) {
+            self.index[node] = Some(self.next_index);
+            self.lowlink[node] = self.next_index;
+            self.next_index += 1;
+            self.stack.push(node);
+            self.on_stack[node] = true;
+
+            for succ in self.graph[node].iter().copied() {
+                if let Some(succ_index) = self.index[succ] {
+                    if self.on_stack[succ] {
+                        self.lowlink[node] = min(self.lowlink[node], succ_index);
+                    }
+                } else {
+                    self.visit(succ);
+                    self.lowlink[node] = min(self.lowlink[node], self.lowlink[succ]);
+                }
+            }
+
+            if Some(self.lowlink[node]) == self.index[node] {
+                let mut scc = vec![];
+                loop {
+                    let member = self.stack.pop().unwrap();
+                    self.on_stack[member] = false;
+                    scc.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                scc.sort();
+                self.sccs.push(scc);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        graph,
+        index: vec![None; graph.len()],
+        lowlink: vec![0; graph.len()],
+        on_stack: vec![false; graph.len()],
+        stack: vec![],
+        next_index: 0,
+        sccs: vec![],
+    };
+    for node in 0..graph.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.visit(node);
+        }
+    }
+    tarjan.sccs
+}
+
+pub fn summarize<V, A, W>(
+    program: &ProgramAST<'_>,
+    initial: V,
+    analyze: A,
+    widen: W,
+) -> Result<BTreeMap<IdentifierId, V>, IdentifierId>
+where
+    V: Copy + PartialEq,
+    A: Fn(&FunctionAST<'_>, &BTreeMap<IdentifierId, V>) -> Result<V, IdentifierId>,
+    W: Fn(&V, &V) -> V,
+{
+    let funcs = program.funcs.as_ref();
+    let graph = call_graph(program);
+    let mut summaries = BTreeMap::new();
+    for scc in call_sccs(&graph) {
+        let recursive = scc.len() > 1 || graph[scc[0]].contains(&scc[0]);
+        if !recursive {
+            let func = &funcs[scc[0]];
+            let summary = analyze(func, &summaries)?;
+            summaries.insert(func.name, summary);
+            continue;
+        }
+
+        for idx in &scc {
+            summaries.insert(funcs[*idx].name, initial);
+        }
+        loop {
+            let mut changed = false;
+            for idx in &scc {
+                let func = &funcs[*idx];
+                let old = summaries[&func.name];
+                let new = widen(&old, &analyze(func, &summaries)?);
+                if new != old {
+                    summaries.insert(func.name, new);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+    Ok(summaries)
 }
 
-fn ai_block<AD: AbstractDomain>(block: &BlockAST<'_>, ad: &AD) -> AD {
+pub(crate) fn expr_depth(expr: &ExpressionAST<'_>) -> usize {
+    use ExpressionAST::*;
+    let mut max_depth = 0;
+    let mut exprs = vec![(expr, 1)];
+    while let Some((expr, depth)) = exprs.pop() {
+        max_depth = max(max_depth, depth);
+        match expr {
+            NumberLiteral(_) | Variable(_) => {}
+            Call(_, args) => exprs.extend(args.as_ref().iter().map(|arg| (arg, depth + 1))),
+            Add(lhs, rhs)
+            | Subtract(lhs, rhs)
+            | Multiply(lhs, rhs)
+            | Divide(lhs, rhs)
+            | Modulo(lhs, rhs)
+            | EqualsEquals(lhs, rhs)
+            | NotEquals(lhs, rhs)
+            | Less(lhs, rhs)
+            | LessEquals(lhs, rhs)
+            | Greater(lhs, rhs)
+            | GreaterEquals(lhs, rhs) => {
+                exprs.push((lhs, depth + 1));
+                exprs.push((rhs, depth + 1));
+            }
+            BlockExpr(block, value) => {
+                exprs.push((value, depth + 1));
+                let mut blocks = vec![block];
+                while let Some(block) = blocks.pop() {
+                    for stmt in block.stmts.as_ref() {
+                        match stmt {
+                            StatementAST::Block(block) => blocks.push(block),
+                            StatementAST::Assign(_, expr) | StatementAST::Return(expr) => {
+                                exprs.push((expr, depth + 1))
+                            }
+                            StatementAST::IfElse(cond, lhs, rhs) => {
+                                exprs.push((cond, depth + 1));
+                                blocks.push(lhs);
+                                blocks.extend(rhs);
+                            }
+                            StatementAST::While(cond, body) => {
+                                exprs.push((cond, depth + 1));
+                                blocks.push(body);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    max_depth
+}
+
+fn interp_guarded<AD: AbstractDomain>(ad: &AD, expr: &ExpressionAST<'_>) -> AD::Value {
+    if expr_depth(expr) > ad.max_expr_depth() {
+        ad.opaque_expr(expr)
+    } else {
+        ad.interp_expr(expr)
+    }
+}
+
+fn branch_guarded<AD: AbstractDomain>(
+    ad: &AD,
+    cond: &ExpressionAST<'_>,
+    value: AD::Value,
+) -> (AD, AD) {
+    if expr_depth(cond) > ad.max_expr_depth() {
+        // Refining on a condition this deep would recurse into it, so branch on an
+        // expression no domain refines on instead.
+        ad.branch(&ExpressionAST::NumberLiteral(1), value)
+    } else {
+        ad.branch(cond, value)
+    }
+}
+
+/// Fails with the offending variable if a loop's widened state stops ascending, which would
+/// otherwise spin forever on a domain whose `widen` isn't extensive.
+pub(crate) fn ai_block<AD: AbstractDomain>(
+    block: &BlockAST<'_>,
+    ad: &AD,
+) -> Result<AD, IdentifierId> {
     let mut ad = ad.clone();
     for stmt in block.stmts.as_ref() {
-        ad = ai_stmt(stmt, &ad);
+        ad = ai_stmt(stmt, &ad)?;
     }
-    ad
+    Ok(ad)
+}
+
+// The block runs on a copy of `ad` and only its value escapes, which the parser makes sound by
+// rejecting block expressions with control flow or assignments to variables used elsewhere.
+pub(crate) fn ai_block_expr<AD: AbstractDomain>(
+    block: &BlockAST<'_>,
+    value: &ExpressionAST<'_>,
+    ad: &AD,
+) -> AD::Value {
+    ai_block(block, ad)
+        .expect("block expressions contain no loops")
+        .interp_expr(value)
 }
 
-fn ai_stmt<AD: AbstractDomain>(stmt: &StatementAST<'_>, ad: &AD) -> AD {
+fn ai_stmt<AD: AbstractDomain>(stmt: &StatementAST<'_>, ad: &AD) -> Result<AD, IdentifierId> {
     use StatementAST::*;
-    match stmt {
-        Block(block) => ai_block(block, ad),
+    Ok(match stmt {
+        Block(block) => ai_block(block, ad)?,
         Assign(iden, expr) => {
             let mut ad = ad.clone();
-            let value = ad.interp_expr(expr);
+            let value = interp_guarded(&ad, expr);
             ad.assign(*iden, value);
             ad
         }
         IfElse(cond, lhs, rhs) => {
-            let value = ad.interp_expr(cond);
-            let (true_ad, mut false_ad) = ad.branch(value);
-            let true_ad = ai_block(lhs, &true_ad);
+            let value = interp_guarded(ad, cond);
+            let (true_ad, mut false_ad) = branch_guarded(ad, cond, value);
+            let true_ad = ai_block(lhs, &true_ad)?;
+            // Without an else block, the false edge carries the pre-branch state (refined by the
+            // condition) straight to the join.
             if let Some(rhs) = rhs {
-                false_ad = ai_block(rhs, &false_ad);
+                false_ad = ai_block(rhs, &false_ad)?;
             }
             true_ad.join(&false_ad)
         }
         While(cond, body) => {
             let mut iter = ad.clone();
+            let mut last_top: Option<AD> = None;
             loop {
+                // The loop entry is region input 0 and the back-edge is input 1, so only the
+                // back-edge state is widened into the entry state.
                 let (top, widening) = ad.widen(&iter);
-                let cond = top.interp_expr(cond);
-                let (cont, exit) = top.branch(cond);
-                let bottom = ai_block(body, &cont);
+                if let Some(last_top) = &last_top {
+                    top.ascends_from(last_top)?;
+                }
+                let value = interp_guarded(&top, cond);
+                let (cont, exit) = branch_guarded(&top, cond, value);
+                let bottom = ai_block(body, &cont)?;
+                last_top = Some(top);
                 if bottom == iter && !widening {
-                    break exit;
+                    let Some(top) = ad.narrow(&iter) else {
+                        break exit;
+                    };
+                    let value = interp_guarded(&top, cond);
+                    break branch_guarded(&top, cond, value).1;
                 } else {
                     iter = bottom;
                 }
@@ -93,11 +429,11 @@ fn ai_stmt<AD: AbstractDomain>(stmt: &StatementAST<'_>, ad: &AD) -> AD {
         }
         Return(expr) => {
             let mut ad = ad.clone();
-            let value = ad.interp_expr(expr);
+            let value = interp_guarded(&ad, expr);
             ad.finish_with(value);
             ad
         }
-    }
+    })
 }
 
 #[cfg(test)]
@@ -120,13 +456,281 @@ mod tests {
 
         let program = "fn basic(x) { while x { x = x + -1; } return x; }";
         let program = ProgramParser::new()
-            .parse(&arena, &mut interner, &program)
+            .parse(&arena, &mut interner, program)
             .unwrap();
-        let mut graphs = abstract_interpret(&program, &mut interner);
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
         graphs[0].rebuild();
         assert_eq!(
             graphs[0].dump(&interner),
-            "cons([4294967295]) -> [6]\nparam([0, 0]) -> [1]\nstart([]) -> [0]\nregion([0, 4]) -> [2]\nregion([0, 11]) -> [2]\nbranch([2, 1]) -> [3]\nbranch([2, 8]) -> [10]\nπ([3, 1]) -> [4]\nπ([3, 0]) -> [5]\nπ([10, 1]) -> [11]\nπ([10, 0]) -> [12]\nfinish([12, 8]) -> [27]\nϕ([2, 1, 7]) -> [9]\nϕ([2, 1, 14]) -> [8]\n+([1, 6]) -> [7]\n+([8, 6]) -> [14]\n",
+            "cons([-1]) -> [6]\nparam([0, 0]) -> [1]\nstart([]) -> [0]\nregion([0, 4]) -> [2]\nregion([0, 11]) -> [2]\nbranch([2, 1]) -> [3]\nbranch([2, 8]) -> [10]\nπ([3, 1]) -> [4]\nπ([3, 0]) -> [5]\nπ([10, 1]) -> [11]\nπ([10, 0]) -> [12]\nfinish([12, 8]) -> [27]\nϕ([2, 1, 7]) -> [9]\nϕ([2, 1, 14]) -> [8]\n+([1, 6]) -> [7]\n+([8, 6]) -> [14]\n",
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn multiple_returns() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn two(x) { if x { return 1; } else { return 2; } }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        graphs[0].rebuild();
+        assert_eq!(graphs[0].return_sites().count(), 2);
+        let ret = graphs[0].return_value().unwrap();
+        assert!(
+            graphs[0]
+                .terms()
+                .any(|term| matches!(term, Term::Phi { root, .. } if graphs[0].find(root) == ret))
+        );
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Oscillating {
+        iden: IdentifierId,
+        value: i32,
+    }
+
+    impl AbstractDomain for Oscillating {
+        type Value = i32;
+
+        fn interp_expr(&self, expr: &ExpressionAST<'_>) -> i32 {
+            match expr {
+                ExpressionAST::Add(..) => self.value + 1,
+                _ => self.value,
+            }
+        }
+
+        fn opaque_expr(&self, _expr: &ExpressionAST<'_>) -> i32 {
+            self.value
+        }
+
+        fn get(&self, _iden: IdentifierId) -> i32 {
+            self.value
+        }
+
+        fn assign(&mut self, _iden: IdentifierId, val: i32) {
+            self.value = val;
+        }
+
+        fn branch(&self, _cond: &ExpressionAST<'_>, _value: i32) -> (Self, Self) {
+            (self.clone(), self.clone())
+        }
+
+        fn finish_with(&mut self, _val: i32) {}
+
+        fn join(&self, other: &Self) -> Self {
+            other.clone()
+        }
+
+        fn widen(&self, back_edge: &Self) -> (Self, bool) {
+            let value = if back_edge.value % 2 == 0 { 10 } else { 5 };
+            (Self { value, ..*self }, false)
+        }
+
+        fn ascends_from(&self, previous: &Self) -> Result<(), IdentifierId> {
+            if self.value >= previous.value {
+                Ok(())
+            } else {
+                Err(self.iden)
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn broken_widen() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn spin(x) { while x { x = x + 1; } return x; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let x = interner.intern("x");
+        let ad = Oscillating { iden: x, value: 0 };
+        assert_eq!(
+            ai_block(&program.funcs.as_ref()[0].block, &ad).err(),
+            Some(x)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn missing_returns() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        assert!(
+            abstract_interpret(&program, &mut interner)
+                .unwrap()
+                .is_empty()
+        );
+
+        let program =
+            "fn empty() {} fn no_return(x) { y = x + 1; } fn maybe(x) { if x { return 1; } }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        let finishes = [1, 1, 2];
+        for (graph, finishes) in graphs.iter_mut().zip(finishes) {
+            graph.rebuild();
+            assert_eq!(graph.return_sites().count(), finishes);
+            assert!(graph.check_control_uniqueness().is_ok());
+        }
+        for graph in &graphs[0..2] {
+            let ret = graph.return_value().unwrap();
+            assert!(
+                graph.terms().any(
+                    |term| matches!(term, Term::Opaque { root, .. } if graph.find(root) == ret)
+                )
+            );
+        }
+        let ret = graphs[2].return_value().unwrap();
+        assert!(
+            graphs[2]
+                .terms()
+                .any(|term| matches!(term, Term::Phi { root, .. } if graphs[2].find(root) == ret))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn dump_all() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn one() { return 1; } fn inc(x) { return x + 1; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        for graph in graphs.iter_mut() {
+            graph.rebuild();
+        }
+        assert_eq!(
+            graphs.names(),
+            &[interner.intern("one"), interner.intern("inc")]
+        );
+        let dump = graphs.dump_all(&interner);
+        let one = format!("one:\n{}", graphs[0].dump(&interner));
+        let inc = format!("inc:\n{}", graphs[1].dump(&interner));
+        assert_eq!(dump, format!("{}{}", one, inc));
+        assert!(dump.starts_with("one:\ncons([1]) -> "));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn scratch_arena() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn add(x, y) { if x { return x + y; } return y; } fn three(a, b, c) { return a + b + c; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        // Only fits the parameters of one function at a time, so the scratch arena has to be
+        // reset between functions.
+        let mut scratch_buf: [u64; 3] = [0; 3];
+        let mut scratch = Arena::new_backed(&mut scratch_buf);
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        let mut scratch_graphs =
+            abstract_interpret_in(&program, &mut interner, &mut scratch).unwrap();
+        for graph in graphs.iter_mut().chain(scratch_graphs.iter_mut()) {
+            graph.rebuild();
+        }
+        assert_eq!(graphs.names(), scratch_graphs.names());
+        assert_eq!(
+            graphs.dump_all(&interner),
+            scratch_graphs.dump_all(&interner)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn all_terms() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn one() { return 1; } fn inc(x) { return x + 1; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let graphs = abstract_interpret(&program, &mut interner).unwrap();
+        let terms: Vec<(usize, Term)> = super::all_terms(&graphs).collect();
+        assert_eq!(
+            terms.len(),
+            graphs.iter().map(|graph| graph.terms().count()).sum()
+        );
+        assert!(terms.iter().all(|(idx, term)| graphs[*idx].contains(*term)));
+        assert!(
+            terms
+                .iter()
+                .any(|(idx, term)| *idx == 1 && matches!(term, Term::Add { .. }))
+        );
+        assert!(
+            !terms
+                .iter()
+                .any(|(idx, term)| *idx == 0 && matches!(term, Term::Add { .. }))
+        );
+        assert_eq!(
+            terms
+                .iter()
+                .filter(|(_, term)| matches!(term, Term::Start { .. }))
+                .map(|(idx, _)| *idx)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn call_order() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn a(x) { return b(x) + c(x); } fn b(x) { return c(x); } fn c(x) { return d(x); } fn d(x) { return c(x) + d(x); }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let graph = call_graph(&program);
+        assert_eq!(
+            graph,
+            vec![
+                BTreeSet::from([1, 2]),
+                BTreeSet::from([2]),
+                BTreeSet::from([3]),
+                BTreeSet::from([2, 3]),
+            ]
         );
+        assert_eq!(call_sccs(&graph), vec![vec![2, 3], vec![1], vec![0]]);
     }
 }
@@ -1,6 +1,10 @@
+use std::collections::HashMap;
+
 use util::interner::IdentifierId;
 use util::vec::ArenaVec;
 
+use crate::visit::{Visitor, walk_expr, walk_stmt};
+
 #[derive(Debug, Default)]
 pub struct ProgramAST<'a> {
     pub funcs: ArenaVec<'a, FunctionAST<'a>>,
@@ -46,6 +50,8 @@ pub enum ExpressionAST<'a> {
     LessEquals(&'a ExpressionAST<'a>, &'a ExpressionAST<'a>),
     Greater(&'a ExpressionAST<'a>, &'a ExpressionAST<'a>),
     GreaterEquals(&'a ExpressionAST<'a>, &'a ExpressionAST<'a>),
+
+    BlockExpr(BlockAST<'a>, &'a ExpressionAST<'a>),
 }
 
 impl Default for StatementAST<'_> {
@@ -60,14 +66,98 @@ impl Default for ExpressionAST<'_> {
     }
 }
 
+/// Parses a `0x` or `0b` literal, optionally negated. An unsigned literal is a 32-bit pattern, so
+/// `0x80000000` through `0xFFFFFFFF` are negative (`0xFFFFFFFF` is -1) and anything wider is an
+/// error. A negated literal is a magnitude and must not wrap: `-0x80000000` is `i32::MIN`, while
+/// `-0x80000001` and `-0xFFFFFFFF` are errors.
+pub fn parse_radix_literal(literal: &str, radix: u32) -> Option<i32> {
+    let (negative, literal) = match literal.strip_prefix('-') {
+        Some(literal) => (true, literal),
+        None => (false, literal),
+    };
+    let value = u32::from_str_radix(&literal[2..], radix).ok()?;
+    if negative {
+        i32::try_from(-i64::from(value)).ok()
+    } else {
+        Some(value as i32)
+    }
+}
+
+#[derive(Default)]
+struct BlockExprCheck {
+    counts: HashMap<IdentifierId, usize>,
+    assigned: Vec<IdentifierId>,
+    in_block_expr: bool,
+    control_flow: bool,
+    block_exprs: Vec<(HashMap<IdentifierId, usize>, Vec<IdentifierId>)>,
+}
+
+impl Visitor for BlockExprCheck {
+    fn visit_stmt(&mut self, stmt: &StatementAST<'_>) {
+        match stmt {
+            StatementAST::Block(_) => {}
+            StatementAST::Assign(iden, _) => {
+                *self.counts.entry(*iden).or_default() += 1;
+                self.assigned.push(*iden);
+            }
+            StatementAST::IfElse(..) | StatementAST::While(..) | StatementAST::Return(_) => {
+                self.control_flow |= self.in_block_expr
+            }
+        }
+        walk_stmt(stmt, self);
+    }
+
+    fn visit_expr(&mut self, expr: &ExpressionAST<'_>) {
+        match expr {
+            ExpressionAST::Variable(iden) => *self.counts.entry(*iden).or_default() += 1,
+            ExpressionAST::BlockExpr(..) => {
+                let mut inner = BlockExprCheck {
+                    in_block_expr: true,
+                    ..Default::default()
+                };
+                walk_expr(expr, &mut inner);
+                self.control_flow |= inner.control_flow;
+                self.block_exprs.push((inner.counts, inner.assigned));
+            }
+            _ => {}
+        }
+        walk_expr(expr, self);
+    }
+}
+
+/// Block expressions are interpreted on a copy of the surrounding state, so their effects other
+/// than the trailing value are dropped. Only accept blocks where that's unobservable: no `if`,
+/// `while` or `return`, and every variable assigned inside appears nowhere else in the function.
+pub fn check_block_exprs(func: &FunctionAST<'_>) -> Result<(), &'static str> {
+    let mut check = BlockExprCheck::default();
+    for param in func.params.as_ref() {
+        *check.counts.entry(*param).or_default() += 1;
+    }
+    check.visit_func(func);
+    if check.control_flow {
+        return Err("block expression contains control flow");
+    }
+    for (counts, assigned) in &check.block_exprs {
+        if assigned
+            .iter()
+            .any(|iden| counts[iden] != check.counts[iden])
+        {
+            return Err("block expression assigns a variable used outside it");
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use util::arena::Arena;
     use util::interner::StringInterner;
 
+    use super::*;
+
     use crate::grammar::ProgramParser;
 
-    fn get_example_imp_programs() -> Vec<String> {
+    pub(crate) fn get_example_imp_programs() -> Vec<String> {
         let var = "CARGO_MANIFEST_DIR";
         let val = std::env::var(var).unwrap();
         let mut path = std::path::PathBuf::from(val);
@@ -96,4 +186,203 @@ mod tests {
                 .unwrap();
         }
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn parse_block_expr() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+
+        let mut buf: [u8; 100000] = [0; 100000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, "fn f() { x = { y = 1; y + 2 }; }")
+            .unwrap();
+        let stmts = program.funcs.as_ref()[0].block.stmts.as_ref();
+        assert_eq!(stmts.len(), 1);
+        let StatementAST::Assign(x, ExpressionAST::BlockExpr(block, value)) = &stmts[0] else {
+            panic!()
+        };
+        assert_eq!(*x, interner.intern("x"));
+        let y = interner.intern("y");
+        assert!(matches!(
+            block.stmts.as_ref(),
+            [StatementAST::Assign(iden, ExpressionAST::NumberLiteral(1))] if *iden == y
+        ));
+        assert!(matches!(
+            value,
+            ExpressionAST::Add(ExpressionAST::Variable(iden), ExpressionAST::NumberLiteral(2))
+                if *iden == y
+        ));
+
+        assert!(
+            ProgramParser::new()
+                .parse(&arena, &mut interner, "fn f() { x = { y = 1; }; }")
+                .is_err()
+        );
+
+        let mut parse = |program: &str| {
+            ProgramParser::new()
+                .parse(&arena, &mut interner, program)
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        };
+        assert_eq!(
+            parse("fn f() { x = { { y = 1; } z = y; { w = z; } w } + 1; }"),
+            Ok(())
+        );
+        assert_eq!(parse("fn f(a) { x = { y = { z = a; z }; y }; }"), Ok(()));
+        for (program, error) in [
+            ("fn f() { y = 0; x = { y = 1; y }; }", "assigns a variable"),
+            ("fn f(y) { x = { y = 1; y }; }", "assigns a variable"),
+            (
+                "fn f() { x = { y = 1; y }; return y; }",
+                "assigns a variable",
+            ),
+            (
+                "fn f() { x = { y = 1; y } + { y = 2; y }; }",
+                "assigns a variable",
+            ),
+            ("fn f(a) { x = { if a { y = 1; } 0 }; }", "control flow"),
+            ("fn f(a) { x = { while a { a = 0; } 0 }; }", "control flow"),
+            ("fn f() { x = { y = { return 1; 0 }; y }; }", "control flow"),
+        ] {
+            assert!(parse(program).unwrap_err().contains(error), "{}", program);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn parse_radix_literals() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let mut parse = |literal: &str| {
+            let program = format!("fn f() {{ return {}; }}", literal);
+            let program = ProgramParser::new()
+                .parse(&arena, &mut interner, &program)
+                .ok()?;
+            let stmts = program.funcs.as_ref()[0].block.stmts.as_ref();
+            let [StatementAST::Return(ExpressionAST::NumberLiteral(value))] = stmts else {
+                panic!()
+            };
+            Some(*value)
+        };
+        assert_eq!(parse("0x1F"), Some(31));
+        assert_eq!(parse("0xff"), Some(255));
+        assert_eq!(parse("-0x10"), Some(-16));
+        assert_eq!(parse("0b1010"), Some(10));
+        assert_eq!(parse("-0b1"), Some(-1));
+        assert_eq!(parse("0x7FFFFFFF"), Some(i32::MAX));
+        assert_eq!(parse("0x80000000"), Some(i32::MIN));
+        assert_eq!(parse("0xFFFFFFFF"), Some(-1));
+        assert_eq!(parse("-0x7FFFFFFF"), Some(-i32::MAX));
+        assert_eq!(parse("-0x80000000"), Some(i32::MIN));
+        assert!(parse("-0x80000001").is_none());
+        assert!(parse("-0xFFFFFFFF").is_none());
+        assert!(parse("-0x100000000").is_none());
+        assert_eq!(parse("0b11111111111111111111111111111111"), Some(-1));
+        assert_eq!(parse("-0b10000000000000000000000000000000"), Some(i32::MIN));
+        assert!(parse("-0b10000000000000000000000000000001").is_none());
+        assert!(parse("0x100000000").is_none());
+        assert!(parse("0b100000000000000000000000000000000").is_none());
+        assert_eq!(parse("42"), Some(42));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn parse_comments() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let plain = "fn f(x) { y = x + 1; return y / 2; }";
+        let commented = "// leading comment
+            fn f(x /* the input */) {
+                y = x /* inline */ + // trailing
+                    1;
+                /* between
+                   statements */
+                return y / /**/ 2; /* a ** b */
+            } // done";
+        let plain = ProgramParser::new()
+            .parse(&arena, &mut interner, plain)
+            .unwrap();
+        let commented = ProgramParser::new()
+            .parse(&arena, &mut interner, commented)
+            .unwrap();
+        assert_eq!(format!("{:?}", plain), format!("{:?}", commented));
+
+        assert!(
+            ProgramParser::new()
+                .parse(&arena, &mut interner, "fn f() { /* unterminated }")
+                .is_err()
+        );
+        assert!(
+            ProgramParser::new()
+                .parse(&arena, &mut interner, "fn f() { /* /* nested */ */ }")
+                .is_err()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn parse_else_if() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = ProgramParser::new()
+            .parse(
+                &arena,
+                &mut interner,
+                "fn f(a, b) { if a {} else if b {} else {} }",
+            )
+            .unwrap();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        let stmts = program.funcs.as_ref()[0].block.stmts.as_ref();
+        let [StatementAST::IfElse(ExpressionAST::Variable(cond), then, Some(otherwise))] = stmts
+        else {
+            panic!()
+        };
+        assert_eq!(*cond, a);
+        assert!(then.stmts.is_empty());
+        let [StatementAST::IfElse(ExpressionAST::Variable(cond), then, Some(otherwise))] =
+            otherwise.stmts.as_ref()
+        else {
+            panic!()
+        };
+        assert_eq!(*cond, b);
+        assert!(then.stmts.is_empty());
+        assert!(otherwise.stmts.is_empty());
+
+        let sugared = ProgramParser::new()
+            .parse(
+                &arena,
+                &mut interner,
+                "fn f(a, b) { if a { x = 1; } else if b { x = 2; } }",
+            )
+            .unwrap();
+        let nested = ProgramParser::new()
+            .parse(
+                &arena,
+                &mut interner,
+                "fn f(a, b) { if a { x = 1; } else { if b { x = 2; } } }",
+            )
+            .unwrap();
+        assert_eq!(format!("{:?}", sugared), format!("{:?}", nested));
+    }
 }
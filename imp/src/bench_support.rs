@@ -0,0 +1,52 @@
+use util::interner::StringInterner;
+use util::union_find::ClassId;
+
+use crate::ssa::Graph;
+
+pub fn build_chain(interner: &mut StringInterner, n: u32) -> (Graph, ClassId) {
+    let mut graph = Graph::new(interner);
+    let mut last = graph.constant(0);
+    for idx in 1..=n {
+        let value = graph.constant(idx as i32);
+        last = graph.add(last, value);
+    }
+    (graph, last)
+}
+
+pub fn build_tree(interner: &mut StringInterner, depth: u32) -> (Graph, ClassId) {
+    let mut graph = Graph::new(interner);
+    let mut level: Vec<ClassId> = (0..1 << depth).map(|idx| graph.constant(idx)).collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| graph.add(pair[0], pair[1]))
+            .collect();
+    }
+    (graph, level[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use util::arena::Arena;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn build_graphs() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let (graph, root) = build_chain(&mut interner, 10);
+        assert_eq!(graph.num_enodes(), 21);
+        assert_eq!(graph.num_classes(), 21);
+        assert_eq!(graph.find(root), root);
+
+        let (graph, _) = build_chain(&mut interner, 0);
+        assert_eq!(graph.num_enodes(), 1);
+
+        let (graph, _) = build_tree(&mut interner, 4);
+        assert_eq!(graph.num_enodes(), 16 + 15);
+    }
+}
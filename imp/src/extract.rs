@@ -0,0 +1,158 @@
+use core::cmp::max;
+use std::collections::BTreeMap;
+
+use db::rebuild::ENode;
+use util::bitset::BitSet;
+use util::union_find::ClassId;
+
+use crate::ssa::{Graph, Term};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostModel {
+    pub constant: u64,
+    pub param: u64,
+    pub start: u64,
+    pub region: u64,
+    pub branch: u64,
+    pub control_proj: u64,
+    pub finish: u64,
+    pub phi: u64,
+    pub add: u64,
+    pub subtract: u64,
+    pub multiply: u64,
+    pub divide: u64,
+    pub compare: u64,
+    pub opaque: u64,
+    pub depth_penalty: u64,
+}
+
+impl CostModel {
+    pub fn cost(&self, term: &Term) -> u64 {
+        match term {
+            Term::Constant { .. } => self.constant,
+            Term::Param { .. } => self.param,
+            Term::Start { .. } => self.start,
+            Term::Region { .. } => self.region,
+            Term::Branch { .. } => self.branch,
+            Term::ControlProj { .. } => self.control_proj,
+            Term::Finish { .. } => self.finish,
+            Term::Phi { .. } => self.phi,
+            Term::Add { .. } => self.add,
+            Term::Subtract { .. } => self.subtract,
+            Term::Multiply { .. } => self.multiply,
+            Term::Divide { .. } => self.divide,
+            Term::Equals { .. }
+            | Term::NotEquals { .. }
+            | Term::Less { .. }
+            | Term::LessEquals { .. } => self.compare,
+            Term::Opaque { .. } => self.opaque,
+        }
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            constant: 0,
+            param: 0,
+            start: 0,
+            region: 1,
+            branch: 1,
+            control_proj: 0,
+            finish: 1,
+            phi: 1,
+            add: 1,
+            subtract: 1,
+            multiply: 1,
+            divide: 1,
+            compare: 1,
+            opaque: 1,
+            depth_penalty: 0,
+        }
+    }
+}
+
+impl Graph {
+    pub fn extract(&self, costs: &CostModel) -> BTreeMap<ClassId, Term> {
+        let mut best: BTreeMap<ClassId, (u64, u64, Term)> = BTreeMap::new();
+        let mut extracted = BitSet::with_capacity(self.num_classes() as usize);
+        loop {
+            let mut changed = false;
+            for term in self.terms() {
+                let root = self.find(term.root());
+                let operands: Vec<ClassId> = term
+                    .operands()
+                    .into_iter()
+                    .map(|id| self.find(id))
+                    .collect();
+                if !operands
+                    .iter()
+                    .all(|id| extracted.contains(id.idx() as usize))
+                {
+                    continue;
+                }
+                let mut cost = costs.cost(&term);
+                let mut height = 0;
+                for operand in operands {
+                    let (operand_cost, operand_height, _) = best[&operand];
+                    cost = cost.saturating_add(operand_cost);
+                    height = max(height, operand_height);
+                }
+                height += 1;
+                let cost = cost.saturating_add(costs.depth_penalty.saturating_mul(height));
+                if best
+                    .get(&root)
+                    .is_none_or(|(old_cost, _, _)| cost < *old_cost)
+                {
+                    best.insert(root, (cost, height, term));
+                    extracted.insert(root.idx() as usize);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+        best.into_iter()
+            .map(|(root, (_, _, term))| (root, term))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use util::arena::Arena;
+    use util::interner::StringInterner;
+
+    #[test]
+    fn weighted_extract() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let x = db.constant(1);
+        let y = db.constant(2);
+        let region = db.region(start, start);
+        let add = db.add(x, y);
+        let phi = db.phi(region, x, y);
+        db.merge_mut(add, phi);
+        db.rebuild();
+        let class = db.find(add);
+
+        let chosen = db.extract(&CostModel::default());
+        assert!(matches!(chosen[&class], Term::Add { .. }));
+
+        let costs = CostModel {
+            add: 5,
+            ..CostModel::default()
+        };
+        let chosen = db.extract(&costs);
+        assert!(matches!(chosen[&class], Term::Phi { .. }));
+    }
+}
@@ -1,10 +1,13 @@
 use core::cmp::{max, min};
+use core::fmt;
 use std::collections::BTreeMap;
 
+use db::rebuild::ENode;
 use util::interner::IdentifierId;
 
-use crate::ai::AbstractDomain;
-use crate::ast::ExpressionAST;
+use crate::ai::{AbstractDomain, DEFAULT_MAX_EXPR_DEPTH, ai_block, ai_block_expr, summarize};
+use crate::ast::{ExpressionAST, ProgramAST};
+use crate::ssa::{Graph, Term};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Interval {
@@ -27,6 +30,43 @@ impl Interval {
         }
     }
 
+    pub(crate) fn from_bounds(low: i32, high: i32) -> Self {
+        Self { low, high }
+    }
+
+    pub(crate) fn bounds(&self) -> (i32, i32) {
+        (self.low, self.high)
+    }
+
+    pub fn from_const(value: i32) -> Self {
+        Self {
+            low: value,
+            high: value,
+        }
+    }
+
+    fn at_most(high: Option<i32>) -> Self {
+        high.map_or(Self::top(), |high| Self {
+            low: i32::MIN,
+            high,
+        })
+    }
+
+    fn at_least(low: Option<i32>) -> Self {
+        low.map_or(Self::top(), |low| Self {
+            low,
+            high: i32::MAX,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.low > self.high
+    }
+
+    pub fn contains(&self, value: i32) -> bool {
+        self.low <= value && value <= self.high
+    }
+
     fn join(&self, other: &Interval) -> Self {
         Self {
             low: min(self.low, other.low),
@@ -34,76 +74,247 @@ impl Interval {
         }
     }
 
-    fn meet(&self, other: &Interval) -> Self {
+    pub(crate) fn meet(&self, other: &Interval) -> Self {
         let met = Self {
             low: max(self.low, other.low),
             high: min(self.high, other.high),
         };
-        if met.low > met.high { Self::top() } else { met }
+        if met.is_empty() { Self::top() } else { met }
+    }
+
+    pub fn add(&self, other: &Interval) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return Self::top();
+        }
+        Self {
+            low: self.low.saturating_add(other.low),
+            high: self.high.saturating_add(other.high),
+        }
     }
 
-    fn widen(&self, other: &Interval) -> Self {
+    fn boolean() -> Self {
+        Self { low: 0, high: 1 }
+    }
+
+    pub fn scale(&self, k: i32) -> Self {
+        if self.is_empty() {
+            return Self::top();
+        }
+        let low = self.low.saturating_mul(k);
+        let high = self.high.saturating_mul(k);
+        if k < 0 {
+            Self {
+                low: high,
+                high: low,
+            }
+        } else {
+            Self { low, high }
+        }
+    }
+
+    pub fn mul(&self, other: &Interval) -> Self {
+        if let Some(k) = other.singleton() {
+            self.scale(k)
+        } else if let Some(k) = self.singleton() {
+            other.scale(k)
+        } else if self.is_empty() || other.is_empty() {
+            Self::top()
+        } else {
+            Self::bottom()
+        }
+    }
+
+    pub fn div(&self, other: &Interval) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return Self::top();
+        }
+        if other.contains(0) {
+            return Self::bottom();
+        }
+        let corners = [
+            self.low.checked_div(other.low),
+            self.low.checked_div(other.high),
+            self.high.checked_div(other.low),
+            self.high.checked_div(other.high),
+        ];
+        if corners.iter().any(Option::is_none) {
+            return Self::bottom();
+        }
+        let corners = corners.map(Option::unwrap);
+        Self {
+            low: *corners.iter().min().unwrap(),
+            high: *corners.iter().max().unwrap(),
+        }
+    }
+
+    fn singleton(&self) -> Option<i32> {
+        (self.low == self.high).then_some(self.low)
+    }
+
+    fn widen(&self, other: &Interval, strategy: fn(i32, i32) -> i32) -> Self {
         Self {
             low: if self.low <= other.low {
                 self.low
             } else {
-                i32::MIN
+                strategy(self.low, other.low).min(other.low)
             },
             high: if self.high >= other.high {
                 self.high
             } else {
-                i32::MAX
+                strategy(self.high, other.high).max(other.high)
             },
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct IntervalDomain {
+pub fn widen_to_infinity(old: i32, new: i32) -> i32 {
+    if new > old { i32::MAX } else { i32::MIN }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            write!(f, "[]")
+        } else {
+            write!(f, "[{}, {}]", self.low, self.high)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IntervalDomain<'a> {
     intervals: BTreeMap<IdentifierId, Interval>,
     finished: Option<Interval>,
+    returned: Option<Interval>,
+    summaries: &'a BTreeMap<IdentifierId, Interval>,
+    widen_delay: usize,
+    iterations: usize,
+    max_expr_depth: usize,
+    widen_strategy: fn(i32, i32) -> i32,
 }
 
-impl IntervalDomain {
-    pub fn new(params: Vec<IdentifierId>) -> Self {
+impl<'a> IntervalDomain<'a> {
+    pub fn new(
+        params: impl IntoIterator<Item = IdentifierId>,
+        summaries: &'a BTreeMap<IdentifierId, Interval>,
+    ) -> Self {
         Self {
             intervals: params
                 .into_iter()
                 .map(|iden| (iden, Interval::bottom()))
                 .collect(),
             finished: None,
+            returned: None,
+            summaries,
+            widen_delay: 0,
+            iterations: 0,
+            max_expr_depth: DEFAULT_MAX_EXPR_DEPTH,
+            widen_strategy: widen_to_infinity,
+        }
+    }
+
+    pub fn with_widen_delay(self, widen_delay: usize) -> Self {
+        Self {
+            widen_delay,
+            ..self
+        }
+    }
+
+    pub fn with_widen_strategy(self, widen_strategy: fn(i32, i32) -> i32) -> Self {
+        Self {
+            widen_strategy,
+            ..self
+        }
+    }
+
+    pub fn with_max_expr_depth(self, max_expr_depth: usize) -> Self {
+        Self {
+            max_expr_depth,
+            ..self
+        }
+    }
+
+    pub fn returned(&self) -> Option<Interval> {
+        self.returned
+    }
+
+    fn refine(&mut self, iden: IdentifierId, interval: Interval) {
+        let refined = self.get(iden).meet(&interval);
+        self.assign(iden, refined);
+    }
+
+    fn join_returned(&self, other: &Self) -> Option<Interval> {
+        match (self.returned, other.returned) {
+            (Some(lhs), Some(rhs)) => Some(lhs.join(&rhs)),
+            (lhs, rhs) => lhs.or(rhs),
         }
     }
 }
 
-impl AbstractDomain for IntervalDomain {
+impl PartialEq for IntervalDomain<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.intervals == other.intervals
+            && self.finished == other.finished
+            && self.returned == other.returned
+    }
+}
+
+impl AbstractDomain for IntervalDomain<'_> {
     type Value = Interval;
 
     fn interp_expr(&self, expr: &ExpressionAST<'_>) -> Interval {
         use ExpressionAST::*;
         match expr {
-            NumberLiteral(value) => Interval {
-                low: *value,
-                high: *value,
-            },
+            NumberLiteral(value) => Interval::from_const(*value),
             Variable(iden) => self.get(*iden),
-            Add(lhs, rhs) => {
+            Call(callee, _) => self
+                .summaries
+                .get(callee)
+                .copied()
+                .unwrap_or(Interval::bottom()),
+            Add(lhs, rhs) => self.interp_expr(lhs).add(&self.interp_expr(rhs)),
+            Multiply(lhs, rhs) => self.interp_expr(lhs).mul(&self.interp_expr(rhs)),
+            Divide(lhs, rhs) => self.interp_expr(lhs).div(&self.interp_expr(rhs)),
+            Modulo(lhs, rhs) => {
                 let lhs = self.interp_expr(lhs);
                 let rhs = self.interp_expr(rhs);
-                Interval {
-                    low: lhs.low.saturating_add(rhs.low),
-                    high: lhs.high.saturating_add(rhs.high),
+                if lhs.is_empty() || rhs.is_empty() {
+                    return Interval::top();
+                }
+                // Like Rust's `%`, the result takes the sign of the dividend.
+                let sign_bound = Interval {
+                    low: min(lhs.low, 0),
+                    high: max(lhs.high, 0),
+                };
+                if rhs.contains(0) {
+                    return sign_bound;
                 }
-                //if let (Some(low), Some(high)) = (lhs.low.checked_add(rhs.low), lhs.high.checked_add(rhs.high)) {
-                //    Interval { low, high }
-                //} else {
-                //    Interval::bottom()
-                //}
+                let min_abs = min(rhs.low.unsigned_abs(), rhs.high.unsigned_abs());
+                let max_abs = max(rhs.low.unsigned_abs(), rhs.high.unsigned_abs());
+                if lhs.low.unsigned_abs() < min_abs && lhs.high.unsigned_abs() < min_abs {
+                    return lhs;
+                }
+                let bound = (max_abs - 1) as i32;
+                sign_bound.meet(&Interval {
+                    low: -bound,
+                    high: bound,
+                })
             }
-            _ => todo!(),
+            EqualsEquals(..) | NotEquals(..) | Less(..) | LessEquals(..) | Greater(..)
+            | GreaterEquals(..) => Interval::boolean(),
+            BlockExpr(block, value) => ai_block_expr(block, value, self),
+            _ => Interval::bottom(),
         }
     }
 
+    fn opaque_expr(&self, _expr: &ExpressionAST<'_>) -> Interval {
+        Interval::bottom()
+    }
+
+    fn max_expr_depth(&self) -> usize {
+        self.max_expr_depth
+    }
+
     fn get(&self, iden: IdentifierId) -> Interval {
         self.intervals[&iden]
     }
@@ -112,17 +323,94 @@ impl AbstractDomain for IntervalDomain {
         self.intervals.insert(iden, val);
     }
 
-    fn branch(&self, _cond: Interval) -> (Self, Self) {
-        (self.clone(), self.clone())
+    fn branch(&self, cond: &ExpressionAST<'_>, _value: Interval) -> (Self, Self) {
+        use ExpressionAST::*;
+        let mut true_ad = self.clone();
+        let mut false_ad = self.clone();
+        if let EqualsEquals(lhs, rhs) | NotEquals(lhs, rhs) = cond {
+            let refined = if let Variable(iden) = lhs {
+                Some((*iden, self.interp_expr(rhs)))
+            } else if let Variable(iden) = rhs {
+                Some((*iden, self.interp_expr(lhs)))
+            } else {
+                None
+            };
+            if let Some((iden, value)) = refined {
+                let equal_ad = if let EqualsEquals(..) = cond {
+                    &mut true_ad
+                } else {
+                    &mut false_ad
+                };
+                equal_ad.refine(iden, value);
+            }
+        }
+        if let Less(lhs, rhs) | LessEquals(lhs, rhs) | Greater(lhs, rhs) | GreaterEquals(lhs, rhs) =
+            cond
+        {
+            let less = matches!(cond, Less(..) | LessEquals(..));
+            let strict = matches!(cond, Less(..) | Greater(..));
+            let relation = if let Variable(iden) = lhs {
+                Some((*iden, self.interp_expr(rhs), less))
+            } else if let Variable(iden) = rhs {
+                Some((*iden, self.interp_expr(lhs), !less))
+            } else {
+                None
+            };
+            if let Some((iden, other, less)) = relation
+                && !other.is_empty()
+            {
+                let (true_bound, false_bound) = if less {
+                    (
+                        Interval::at_most(if strict {
+                            other.high.checked_sub(1)
+                        } else {
+                            Some(other.high)
+                        }),
+                        Interval::at_least(if strict {
+                            Some(other.low)
+                        } else {
+                            other.low.checked_add(1)
+                        }),
+                    )
+                } else {
+                    (
+                        Interval::at_least(if strict {
+                            other.low.checked_add(1)
+                        } else {
+                            Some(other.low)
+                        }),
+                        Interval::at_most(if strict {
+                            Some(other.high)
+                        } else {
+                            other.high.checked_sub(1)
+                        }),
+                    )
+                };
+                true_ad.refine(iden, true_bound);
+                false_ad.refine(iden, false_bound);
+            }
+        }
+        (true_ad, false_ad)
     }
 
     fn finish_with(&mut self, val: Interval) {
         self.finished = Some(val);
+        self.returned = Some(self.returned.map_or(val, |returned| returned.join(&val)));
     }
 
     fn join(&self, other: &Self) -> Self {
-        assert!(self.finished.is_none());
-        assert!(other.finished.is_none());
+        let returned = self.join_returned(other);
+        if other.finished.is_some() {
+            return IntervalDomain {
+                returned,
+                ..self.clone()
+            };
+        } else if self.finished.is_some() {
+            return IntervalDomain {
+                returned,
+                ..other.clone()
+            };
+        }
         let mut intervals = BTreeMap::new();
         for (self_iden, self_interval) in &self.intervals {
             if let Some(other_interval) = other.intervals.get(self_iden) {
@@ -132,24 +420,632 @@ impl AbstractDomain for IntervalDomain {
         IntervalDomain {
             intervals,
             finished: None,
+            returned,
+            summaries: self.summaries,
+            widen_delay: self.widen_delay,
+            iterations: max(self.iterations, other.iterations),
+            max_expr_depth: self.max_expr_depth,
+            widen_strategy: self.widen_strategy,
         }
     }
 
     fn widen(&self, other: &Self) -> (Self, bool) {
         assert!(self.finished.is_none());
         assert!(other.finished.is_none());
+        if self.widen_delay > 0
+            && other.iterations.saturating_sub(self.iterations) <= self.widen_delay
+        {
+            let mut joined = self.join(other);
+            joined.iterations = other.iterations + 1;
+            return (joined, false);
+        }
         let mut intervals = BTreeMap::new();
         for (self_iden, self_interval) in &self.intervals {
             if let Some(other_interval) = other.intervals.get(self_iden) {
-                intervals.insert(*self_iden, self_interval.widen(other_interval));
+                intervals.insert(
+                    *self_iden,
+                    self_interval.widen(other_interval, self.widen_strategy),
+                );
             }
         }
         (
             IntervalDomain {
                 intervals,
                 finished: None,
+                returned: self.join_returned(other),
+                summaries: self.summaries,
+                widen_delay: self.widen_delay,
+                iterations: other.iterations,
+                max_expr_depth: self.max_expr_depth,
+                widen_strategy: self.widen_strategy,
             },
             false,
         )
     }
+
+    fn narrow(&self, back_edge: &Self) -> Option<Self> {
+        Some(self.join(back_edge))
+    }
+
+    fn ascends_from(&self, previous: &Self) -> Result<(), IdentifierId> {
+        for (iden, previous) in &previous.intervals {
+            if let Some(current) = self.intervals.get(iden)
+                && !previous.is_empty()
+                && (current.low > previous.low || current.high < previous.high)
+            {
+                return Err(*iden);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Graph {
+    pub fn compute_intervals(&mut self) {
+        let terms = self.topo_terms().unwrap_or_else(|_| self.terms().collect());
+        for term in terms {
+            let get = |id| self.class_interval(id).unwrap_or(Interval::bottom());
+            let interval = match term {
+                Term::Constant { value, .. } => Interval::from_const(value),
+                Term::Param { .. } | Term::Opaque { .. } | Term::Subtract { .. } => {
+                    Interval::bottom()
+                }
+                Term::Phi { lhs, rhs, .. } => get(lhs).join(&get(rhs)),
+                Term::Add { lhs, rhs, .. } => get(lhs).add(&get(rhs)),
+                Term::Multiply { lhs, rhs, .. } => get(lhs).mul(&get(rhs)),
+                Term::Divide { lhs, rhs, .. } => get(lhs).div(&get(rhs)),
+                Term::Equals { .. }
+                | Term::NotEquals { .. }
+                | Term::Less { .. }
+                | Term::LessEquals { .. } => Interval::boolean(),
+                _ => continue,
+            };
+            self.set_class_interval(term.root(), interval);
+        }
+    }
+}
+
+pub fn interval_summaries(
+    program: &ProgramAST<'_>,
+) -> Result<BTreeMap<IdentifierId, Interval>, IdentifierId> {
+    interval_summaries_with_widen_delay(program, 0)
+}
+
+pub fn interval_summaries_with_widen_delay(
+    program: &ProgramAST<'_>,
+    widen_delay: usize,
+) -> Result<BTreeMap<IdentifierId, Interval>, IdentifierId> {
+    summarize(
+        program,
+        Interval::top(),
+        |func, summaries| {
+            let params = func.params.as_ref().to_vec();
+            let ad = IntervalDomain::new(params, summaries).with_widen_delay(widen_delay);
+            let mut ad = ai_block(&func.block, &ad)?;
+            if ad.finished.is_none() {
+                ad.finish_with(Interval::bottom());
+            }
+            Ok(ad.returned().unwrap())
+        },
+        |old, new| {
+            if *old == Interval::top() {
+                *new
+            } else {
+                old.widen(new, widen_to_infinity)
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use util::arena::Arena;
+    use util::interner::StringInterner;
+
+    use crate::grammar::ProgramParser;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn call_summaries() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn caller() { y = five() + 1; return y; } fn five() { return 5; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let summaries = interval_summaries(&program).unwrap();
+        assert_eq!(
+            summaries[&interner.intern("five")],
+            Interval { low: 5, high: 5 }
+        );
+        assert_eq!(
+            summaries[&interner.intern("caller")],
+            Interval { low: 6, high: 6 }
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn recursive_summaries() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn count(x) { if x { return count(x) + 1; } return 0; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let summaries = interval_summaries(&program).unwrap();
+        assert_eq!(
+            summaries[&interner.intern("count")],
+            Interval {
+                low: 0,
+                high: i32::MAX
+            }
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn if_without_else() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program =
+            "fn inc(x) { if x >= 2 { if x <= 5 { if x { x = x + 1; } return x; } } return 2; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let summaries = interval_summaries(&program).unwrap();
+        assert_eq!(
+            summaries[&interner.intern("inc")],
+            Interval { low: 2, high: 6 }
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn block_expr() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn block() { x = { y = 1; y + 2 }; return x + 1; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let summaries = interval_summaries(&program).unwrap();
+        assert_eq!(
+            summaries[&interner.intern("block")],
+            Interval { low: 4, high: 4 }
+        );
+    }
+
+    #[test]
+    fn multiply_by_constant() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let x = interner.intern("x");
+        let summaries = BTreeMap::new();
+        let mut ad = IntervalDomain::new(vec![x], &summaries);
+        ad.assign(x, Interval { low: -3, high: 5 });
+
+        let var = ExpressionAST::Variable(x);
+        let two = ExpressionAST::NumberLiteral(2);
+        let minus_two = ExpressionAST::NumberLiteral(-2);
+        let zero = ExpressionAST::NumberLiteral(0);
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&var, &two)),
+            Interval { low: -6, high: 10 }
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&two, &var)),
+            Interval { low: -6, high: 10 }
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&var, &minus_two)),
+            Interval { low: -10, high: 6 }
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&var, &zero)),
+            Interval::from_const(0)
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&var, &var)),
+            Interval::bottom()
+        );
+
+        ad.assign(
+            x,
+            Interval {
+                low: 1,
+                high: i32::MAX,
+            },
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&var, &two)),
+            Interval {
+                low: 2,
+                high: i32::MAX
+            }
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&var, &minus_two)),
+            Interval {
+                low: i32::MIN,
+                high: -2
+            }
+        );
+        assert_eq!(Interval::top().scale(3), Interval::top());
+    }
+
+    #[test]
+    fn divide_by_constant() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let x = interner.intern("x");
+        let summaries = BTreeMap::new();
+        let mut ad = IntervalDomain::new(vec![x], &summaries);
+        ad.assign(x, Interval { low: -7, high: 7 });
+
+        let var = ExpressionAST::Variable(x);
+        let two = ExpressionAST::NumberLiteral(2);
+        let minus_two = ExpressionAST::NumberLiteral(-2);
+        let zero = ExpressionAST::NumberLiteral(0);
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Divide(&var, &two)),
+            Interval { low: -3, high: 3 }
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Divide(&var, &minus_two)),
+            Interval { low: -3, high: 3 }
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Divide(&two, &var)),
+            Interval::bottom()
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Divide(&var, &zero)),
+            Interval::bottom()
+        );
+
+        ad.assign(x, Interval { low: 3, high: 7 });
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Divide(&var, &two)),
+            Interval { low: 1, high: 3 }
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Divide(&var, &minus_two)),
+            Interval { low: -3, high: -1 }
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Divide(&minus_two, &var)),
+            Interval { low: 0, high: 0 }
+        );
+
+        ad.assign(x, Interval::bottom());
+        let minus_one = ExpressionAST::NumberLiteral(-1);
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Divide(&var, &minus_one)),
+            Interval::bottom()
+        );
+    }
+
+    #[test]
+    fn modulo_by_constant() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let x = interner.intern("x");
+        let summaries = BTreeMap::new();
+        let mut ad = IntervalDomain::new(vec![x], &summaries);
+
+        let var = ExpressionAST::Variable(x);
+        let ten = ExpressionAST::NumberLiteral(10);
+        let minus_ten = ExpressionAST::NumberLiteral(-10);
+        let zero = ExpressionAST::NumberLiteral(0);
+        let modulo = |ad: &IntervalDomain, rhs| ad.interp_expr(&ExpressionAST::Modulo(&var, rhs));
+
+        ad.assign(x, Interval { low: 0, high: 100 });
+        assert_eq!(modulo(&ad, &ten), Interval { low: 0, high: 9 });
+        assert_eq!(modulo(&ad, &minus_ten), Interval { low: 0, high: 9 });
+        ad.assign(x, Interval { low: 3, high: 7 });
+        assert_eq!(modulo(&ad, &ten), Interval { low: 3, high: 7 });
+
+        ad.assign(x, Interval { low: -25, high: 4 });
+        assert_eq!(modulo(&ad, &ten), Interval { low: -9, high: 4 });
+        ad.assign(
+            x,
+            Interval {
+                low: -25,
+                high: -12,
+            },
+        );
+        assert_eq!(modulo(&ad, &ten), Interval { low: -9, high: 0 });
+        ad.assign(x, Interval::bottom());
+        assert_eq!(modulo(&ad, &ten), Interval { low: -9, high: 9 });
+
+        assert_eq!(modulo(&ad, &zero), Interval::bottom());
+        ad.assign(x, Interval { low: 2, high: 50 });
+        assert_eq!(modulo(&ad, &zero), Interval { low: 0, high: 50 });
+        let min = ExpressionAST::NumberLiteral(i32::MIN);
+        assert_eq!(modulo(&ad, &min), Interval { low: 2, high: 50 });
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn widen_delay() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn delay(c) { x = 0; y = 0; while c { y = x; x = 1; } return x + y; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let delay = interner.intern("delay");
+        assert_eq!(
+            interval_summaries_with_widen_delay(&program, 0).unwrap()[&delay],
+            Interval {
+                low: 0,
+                high: i32::MAX
+            }
+        );
+        assert_eq!(
+            interval_summaries_with_widen_delay(&program, 1).unwrap()[&delay],
+            Interval {
+                low: 0,
+                high: i32::MAX
+            }
+        );
+        assert_eq!(
+            interval_summaries_with_widen_delay(&program, 2).unwrap()[&delay],
+            Interval { low: 0, high: 2 }
+        );
+    }
+
+    #[test]
+    fn widen_delay_stale_iterations() {
+        let summaries = BTreeMap::new();
+        let ad = IntervalDomain::new([], &summaries).with_widen_delay(1);
+        let later = IntervalDomain {
+            iterations: 3,
+            ..ad.clone()
+        };
+        let (widened, _) = later.widen(&ad);
+        assert_eq!(widened.iterations, 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn missing_returns() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn empty() {} fn maybe(x) { if x { return 1; } } fn always(x) { if x { return 1; } return 2; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let summaries = interval_summaries(&program).unwrap();
+        assert_eq!(summaries[&interner.intern("empty")], Interval::bottom());
+        assert_eq!(summaries[&interner.intern("maybe")], Interval::bottom());
+        assert_eq!(
+            summaries[&interner.intern("always")],
+            Interval { low: 1, high: 2 }
+        );
+    }
+
+    #[test]
+    fn class_intervals() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut graph = Graph::new(&mut interner);
+        let three = graph.constant(3);
+        let four = graph.constant(4);
+        let sum = graph.add(three, four);
+        let cmp = graph.less(three, sum);
+        let x = graph.opaque(0);
+        assert_eq!(graph.class_interval(three), None);
+        graph.compute_intervals();
+        assert_eq!(graph.class_interval(three), Some(Interval::from_const(3)));
+        assert_eq!(graph.class_interval(sum), Some(Interval::from_const(7)));
+        assert_eq!(
+            graph.class_interval(cmp),
+            Some(Interval { low: 0, high: 1 })
+        );
+        assert_eq!(graph.class_interval(x), Some(Interval::bottom()));
+
+        graph.set_class_interval(x, Interval { low: 0, high: 10 });
+        graph.set_class_interval(x, Interval { low: 5, high: 20 });
+        assert_eq!(graph.class_interval(x), Some(Interval { low: 5, high: 10 }));
+        graph.merge_mut(x, sum);
+        graph.rebuild();
+        assert_eq!(graph.class_interval(x), Some(Interval::from_const(7)));
+        assert_eq!(graph.class_interval(sum), Some(Interval::from_const(7)));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn deep_expression() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 100000] = [0; 100000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let depth = 100;
+        let program = format!(
+            "fn deep() {{ x = {}1{}; return x; }}",
+            "(1 + ".repeat(depth),
+            ")".repeat(depth)
+        );
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, &program)
+            .unwrap();
+        let func = &program.funcs.as_ref()[0];
+        let summaries = BTreeMap::new();
+        let ad = IntervalDomain::new(vec![], &summaries);
+        assert_eq!(
+            ai_block(&func.block, &ad).unwrap().returned(),
+            Some(Interval::from_const(depth as i32 + 1))
+        );
+        let ad = ad.with_max_expr_depth(16);
+        assert_eq!(
+            ai_block(&func.block, &ad).unwrap().returned(),
+            Some(Interval::bottom())
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn guarded_loop() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn count() { x = 0; while x < 10 { x = x + 1; } return x; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let count = interner.intern("count");
+        assert_eq!(
+            interval_summaries(&program).unwrap()[&count],
+            Interval { low: 10, high: 10 }
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn widen_strategy() {
+        fn next_power_of_two(old: i32, new: i32) -> i32 {
+            let magnitude = new.unsigned_abs().next_power_of_two();
+            let bound = i32::try_from(magnitude).unwrap_or(i32::MAX);
+            if new > old { bound } else { -bound }
+        }
+
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn count(c) { x = 0; while c { if x < 100 { x = x + 1; } } return x; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let func = &program.funcs.as_ref()[0];
+        let summaries = BTreeMap::new();
+        let interpret =
+            |ad: IntervalDomain<'_>| ai_block(&func.block, &ad).unwrap().returned().unwrap();
+
+        let ad = IntervalDomain::new(func.params.as_ref().iter().copied(), &summaries);
+        assert_eq!(
+            interpret(ad.clone()),
+            Interval {
+                low: 0,
+                high: i32::MAX
+            }
+        );
+        assert_eq!(
+            interpret(ad.with_widen_strategy(next_power_of_two)),
+            Interval { low: 0, high: 128 }
+        );
+    }
+
+    #[test]
+    fn interval_predicates() {
+        let five = Interval::from_const(5);
+        assert_eq!(five, Interval { low: 5, high: 5 });
+        assert!(five.contains(5));
+        assert!(!five.contains(4));
+        assert!(!five.contains(6));
+        assert!(!five.is_empty());
+
+        let range = Interval { low: -3, high: 7 };
+        assert!(range.contains(-3));
+        assert!(range.contains(0));
+        assert!(range.contains(7));
+        assert!(!range.contains(8));
+        assert!(!range.contains(i32::MIN));
+
+        assert!(Interval::top().is_empty());
+        assert!(!Interval::top().contains(0));
+        assert!(!Interval::top().contains(i32::MIN));
+        assert!(!Interval::top().contains(i32::MAX));
+        assert!(!Interval::bottom().is_empty());
+        assert!(Interval::bottom().contains(i32::MIN));
+        assert!(Interval::bottom().contains(i32::MAX));
+        assert!(five.meet(&Interval::from_const(6)).is_empty());
+        assert_eq!(five.add(&range), Interval { low: 2, high: 12 });
+        assert!(Interval::top().add(&Interval::bottom()).is_empty());
+    }
+
+    #[test]
+    fn equality_narrowing() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let x = interner.intern("x");
+        let summaries = BTreeMap::new();
+        let mut ad = IntervalDomain::new(vec![x], &summaries);
+        ad.assign(x, Interval { low: 0, high: 10 });
+
+        let var = ExpressionAST::Variable(x);
+        let five = ExpressionAST::NumberLiteral(5);
+        let cond = ExpressionAST::EqualsEquals(&var, &five);
+        let (true_ad, false_ad) = ad.branch(&cond, ad.interp_expr(&cond));
+        assert_eq!(true_ad.get(x), Interval::from_const(5));
+        assert_eq!(false_ad.get(x), Interval { low: 0, high: 10 });
+        assert_eq!(
+            true_ad.join(&false_ad).get(x),
+            Interval { low: 0, high: 10 }
+        );
+
+        let cond = ExpressionAST::NotEquals(&five, &var);
+        let (true_ad, false_ad) = ad.branch(&cond, ad.interp_expr(&cond));
+        assert_eq!(true_ad.get(x), Interval { low: 0, high: 10 });
+        assert_eq!(false_ad.get(x), Interval::from_const(5));
+
+        let eleven = ExpressionAST::NumberLiteral(11);
+        let cond = ExpressionAST::EqualsEquals(&var, &eleven);
+        let (true_ad, _) = ad.branch(&cond, ad.interp_expr(&cond));
+        assert!(true_ad.get(x).is_empty());
+    }
+
+    #[test]
+    fn display_interval() {
+        assert_eq!(Interval::from_const(3).to_string(), "[3, 3]");
+        assert_eq!(
+            Interval::from_const(-1)
+                .join(&Interval::from_const(4))
+                .to_string(),
+            "[-1, 4]"
+        );
+        assert_eq!(Interval::bottom().to_string(), "[-2147483648, 2147483647]");
+        assert_eq!(Interval::top().to_string(), "[]");
+    }
 }
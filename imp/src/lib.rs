@@ -2,7 +2,14 @@ use lalrpop_util::lalrpop_mod;
 
 pub mod ai;
 pub mod ast;
+pub mod bench_support;
+pub mod extract;
 pub mod interval;
+pub mod rewrite;
+pub mod rules;
+pub mod sign;
 pub mod ssa;
+pub mod visit;
+pub mod viz;
 
 lalrpop_mod!(pub grammar);
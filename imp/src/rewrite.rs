@@ -0,0 +1,237 @@
+use db::rebuild::ENode;
+use util::union_find::ClassId;
+
+use crate::ssa::{Graph, Term};
+
+#[derive(Clone, Copy)]
+pub struct Rewrite {
+    pub name: &'static str,
+    pub rule: fn(&mut Graph, Term) -> Option<ClassId>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Saturated,
+    NodeLimit,
+    IterLimit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowMode {
+    Wrap,
+    Saturate,
+    Refuse,
+}
+
+impl OverflowMode {
+    pub fn add(&self, lhs: i32, rhs: i32) -> Option<i32> {
+        match self {
+            OverflowMode::Wrap => Some(lhs.wrapping_add(rhs)),
+            OverflowMode::Saturate => Some(lhs.saturating_add(rhs)),
+            OverflowMode::Refuse => lhs.checked_add(rhs),
+        }
+    }
+}
+
+impl Graph {
+    pub fn apply_rewrites(&mut self, rules: &[Rewrite]) -> bool {
+        let old_enodes = self.num_enodes();
+        let mut changed = false;
+
+        let terms: Vec<Term> = self.terms().collect();
+        for term in terms {
+            for rewrite in rules {
+                if let Some(id) = (rewrite.rule)(self, term) {
+                    changed = self.merge_mut(term.root(), id).1 || changed;
+                }
+            }
+        }
+        self.rebuild();
+
+        changed || self.num_enodes() != old_enodes
+    }
+
+    pub fn saturate(&mut self, rules: &[Rewrite], max_iters: usize) -> bool {
+        for _ in 0..max_iters {
+            if !self.apply_rewrites(rules) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn saturate_bounded(
+        &mut self,
+        rules: &[Rewrite],
+        max_iters: usize,
+        max_enodes: usize,
+    ) -> StopReason {
+        for _ in 0..max_iters {
+            if self.num_enodes() > max_enodes {
+                return StopReason::NodeLimit;
+            }
+            if !self.apply_rewrites(rules) {
+                return StopReason::Saturated;
+            }
+        }
+        if self.num_enodes() > max_enodes {
+            StopReason::NodeLimit
+        } else {
+            StopReason::IterLimit
+        }
+    }
+
+    pub fn fold_constants(&mut self, overflow: OverflowMode) -> bool {
+        let mut ever_changed = false;
+        loop {
+            let mut changed = false;
+            let terms: Vec<Term> = self.terms().collect();
+            for term in terms {
+                let Term::Add { lhs, rhs, root } = term else {
+                    continue;
+                };
+                let (Some(lhs), Some(rhs)) = (self.constant_value(lhs), self.constant_value(rhs))
+                else {
+                    continue;
+                };
+                if let Some(value) = overflow.add(lhs, rhs) {
+                    let folded = self.constant(value);
+                    changed = self.merge_mut(root, folded).1 || changed;
+                }
+            }
+            self.rebuild();
+            if !changed {
+                return ever_changed;
+            }
+            ever_changed = true;
+        }
+    }
+
+    pub fn is_saturated(&self, rules: &[Rewrite]) -> bool {
+        !self.clone().apply_rewrites(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use util::arena::Arena;
+    use util::interner::StringInterner;
+
+    const FOLD_ADD: Rewrite = Rewrite {
+        name: "fold-add",
+        rule: |graph, term| {
+            let Term::Add { lhs, rhs, .. } = term else {
+                return None;
+            };
+            let lhs = graph.constant_value(lhs)?;
+            let rhs = graph.constant_value(rhs)?;
+            Some(graph.constant(lhs.wrapping_add(rhs)))
+        },
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn saturated() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(2);
+        let b = db.constant(3);
+        let ab = db.add(a, b);
+        let abb = db.add(ab, b);
+        assert!(!db.is_saturated(&[FOLD_ADD]));
+        assert_eq!(db.constant_value(abb), None);
+
+        assert!(db.saturate(&[FOLD_ADD], 10));
+        assert_eq!(db.constant_value(ab), Some(5));
+        assert_eq!(db.constant_value(abb), Some(8));
+        assert!(db.is_saturated(&[FOLD_ADD]));
+
+        let c = db.constant(4);
+        db.add(abb, c);
+        assert!(!db.is_saturated(&[FOLD_ADD]));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn saturate_bounded() {
+        const COUNT_UP: Rewrite = Rewrite {
+            name: "count-up",
+            rule: |graph, term| {
+                let Term::Constant { value, .. } = term else {
+                    return None;
+                };
+                Some(graph.constant(value.wrapping_add(1)))
+            },
+        };
+
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        db.constant(0);
+        assert_eq!(
+            db.saturate_bounded(&[COUNT_UP], 1000, 20),
+            StopReason::NodeLimit
+        );
+        assert!(db.num_enodes() > 20);
+        assert!(db.num_enodes() <= 40);
+
+        let mut db = Graph::new(&mut interner);
+        db.constant(0);
+        assert_eq!(
+            db.saturate_bounded(&[COUNT_UP], 3, 1000),
+            StopReason::IterLimit
+        );
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(2);
+        let b = db.constant(3);
+        db.add(a, b);
+        assert_eq!(
+            db.saturate_bounded(&[FOLD_ADD], 10, 1000),
+            StopReason::Saturated
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn fold_constants() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let build = |interner: &mut StringInterner| {
+            let mut db = Graph::new(interner);
+            let max = db.constant(i32::MAX);
+            let one = db.constant(1);
+            let sum = db.add(max, one);
+            let two = db.constant(2);
+            let sum_two = db.add(one, two);
+            (db, sum, sum_two)
+        };
+
+        let (mut db, sum, sum_two) = build(&mut interner);
+        assert!(db.fold_constants(OverflowMode::Wrap));
+        assert_eq!(db.constant_value(sum), Some(i32::MIN));
+        assert_eq!(db.constant_value(sum_two), Some(3));
+
+        let (mut db, sum, sum_two) = build(&mut interner);
+        assert!(db.fold_constants(OverflowMode::Saturate));
+        assert_eq!(db.constant_value(sum), Some(i32::MAX));
+        let max = db.constant(i32::MAX);
+        assert_eq!(db.find(sum), db.find(max));
+        assert_eq!(db.constant_value(sum_two), Some(3));
+
+        let (mut db, sum, sum_two) = build(&mut interner);
+        assert!(db.fold_constants(OverflowMode::Refuse));
+        assert_eq!(db.constant_value(sum), None);
+        assert_eq!(db.constant_value(sum_two), Some(3));
+        assert!(!db.fold_constants(OverflowMode::Refuse));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::rewrite::Rewrite;
+use crate::ssa::Term;
+
+const ADD_ZERO: Rewrite = Rewrite {
+    name: "add-zero",
+    rule: |graph, term| {
+        let Term::Add { lhs, rhs, .. } = term else {
+            return None;
+        };
+        if graph.constant_value(rhs) == Some(0) {
+            Some(lhs)
+        } else if graph.constant_value(lhs) == Some(0) {
+            Some(rhs)
+        } else {
+            None
+        }
+    },
+};
+
+const ADD_COMMUTE: Rewrite = Rewrite {
+    name: "add-commute",
+    rule: |graph, term| {
+        let Term::Add { lhs, rhs, .. } = term else {
+            return None;
+        };
+        Some(graph.add(rhs, lhs))
+    },
+};
+
+const ADD_ASSOCIATE: Rewrite = Rewrite {
+    name: "add-associate",
+    rule: |graph, term| {
+        let Term::Add { lhs, rhs, .. } = term else {
+            return None;
+        };
+        let (a, b) = graph.add_operands(lhs).next()?;
+        let bc = graph.add(b, rhs);
+        Some(graph.add(a, bc))
+    },
+};
+
+const SUB_SELF: Rewrite = Rewrite {
+    name: "sub-self",
+    rule: |graph, term| {
+        let Term::Subtract { lhs, rhs, .. } = term else {
+            return None;
+        };
+        (graph.find(lhs) == graph.find(rhs)).then(|| graph.constant(0))
+    },
+};
+
+const MUL_ONE: Rewrite = Rewrite {
+    name: "mul-one",
+    rule: |graph, term| {
+        let Term::Multiply { lhs, rhs, .. } = term else {
+            return None;
+        };
+        if graph.constant_value(rhs) == Some(1) {
+            Some(lhs)
+        } else if graph.constant_value(lhs) == Some(1) {
+            Some(rhs)
+        } else {
+            None
+        }
+    },
+};
+
+const MUL_ZERO: Rewrite = Rewrite {
+    name: "mul-zero",
+    rule: |graph, term| {
+        let Term::Multiply { lhs, rhs, .. } = term else {
+            return None;
+        };
+        if graph.constant_value(rhs) == Some(0) {
+            Some(rhs)
+        } else if graph.constant_value(lhs) == Some(0) {
+            Some(lhs)
+        } else {
+            None
+        }
+    },
+};
+
+const MUL_COMMUTE: Rewrite = Rewrite {
+    name: "mul-commute",
+    rule: |graph, term| {
+        let Term::Multiply { lhs, rhs, .. } = term else {
+            return None;
+        };
+        Some(graph.multiply(rhs, lhs))
+    },
+};
+
+const MUL_ASSOCIATE: Rewrite = Rewrite {
+    name: "mul-associate",
+    rule: |graph, term| {
+        let Term::Multiply { lhs, rhs, .. } = term else {
+            return None;
+        };
+        let (a, b) = graph.multiply_operands(lhs).next()?;
+        let bc = graph.multiply(b, rhs);
+        Some(graph.multiply(a, bc))
+    },
+};
+
+const DIV_ONE: Rewrite = Rewrite {
+    name: "div-one",
+    rule: |graph, term| {
+        let Term::Divide { lhs, rhs, .. } = term else {
+            return None;
+        };
+        (graph.constant_value(rhs) == Some(1)).then_some(lhs)
+    },
+};
+
+pub fn arithmetic_rules() -> Vec<Rewrite> {
+    vec![
+        ADD_ZERO,
+        ADD_COMMUTE,
+        ADD_ASSOCIATE,
+        SUB_SELF,
+        MUL_ONE,
+        MUL_ZERO,
+        MUL_COMMUTE,
+        MUL_ASSOCIATE,
+        DIV_ONE,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ssa::Graph;
+    use util::arena::Arena;
+    use util::interner::StringInterner;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn arithmetic_rules() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let x = db.opaque(0);
+        let zero = db.constant(0);
+        let x_zero = db.add(x, zero);
+        let sum = db.add(x_zero, zero);
+        assert_ne!(db.find(sum), db.find(x));
+        assert!(db.saturate(&super::arithmetic_rules(), 10));
+        assert_eq!(db.find(sum), db.find(x));
+
+        let mut db = Graph::new(&mut interner);
+        let x = db.opaque(0);
+        let zero = db.constant(0);
+        let one = db.constant(1);
+        let x_zero = db.add(x, zero);
+        let prod = db.multiply(x_zero, one);
+        let times_zero = db.multiply(zero, x);
+        let diff = db.subtract(x, x);
+        let quot = db.divide(x, one);
+        let one_quot = db.divide(one, x);
+        assert!(db.saturate(&super::arithmetic_rules(), 10));
+        assert_eq!(db.find(prod), db.find(x));
+        assert_eq!(db.find(times_zero), db.find(zero));
+        assert_eq!(db.find(diff), db.find(zero));
+        assert_eq!(db.find(quot), db.find(x));
+        assert_ne!(db.find(one_quot), db.find(x));
+        assert_ne!(db.find(x), db.find(zero));
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.opaque(0);
+        let b = db.opaque(1);
+        let c = db.opaque(2);
+        let ab = db.add(a, b);
+        let ab_c = db.add(ab, c);
+        let cb = db.add(c, b);
+        let cb_a = db.add(cb, a);
+        assert_ne!(db.find(ab_c), db.find(cb_a));
+        assert!(db.saturate(&super::arithmetic_rules(), 10));
+        assert_eq!(db.find(ab_c), db.find(cb_a));
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.opaque(0);
+        let b = db.opaque(1);
+        let c = db.opaque(2);
+        let ab = db.multiply(a, b);
+        let ab_c = db.multiply(ab, c);
+        let cb = db.multiply(c, b);
+        let cb_a = db.multiply(cb, a);
+        let ab_plus_c = db.add(ab, c);
+        assert_ne!(db.find(ab_c), db.find(cb_a));
+        assert!(db.saturate(&super::arithmetic_rules(), 10));
+        assert_eq!(db.find(ab_c), db.find(cb_a));
+        assert_ne!(db.find(ab_plus_c), db.find(ab_c));
+    }
+}
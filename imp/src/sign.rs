@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+
+use util::interner::IdentifierId;
+
+use crate::ai::{AbstractDomain, ai_block, ai_block_expr, summarize};
+use crate::ast::{ExpressionAST, ProgramAST};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sign {
+    Negative,
+    Zero,
+    Positive,
+    Unknown,
+}
+
+impl Sign {
+    pub fn from_const(value: i32) -> Self {
+        match value {
+            ..0 => Sign::Negative,
+            0 => Sign::Zero,
+            1.. => Sign::Positive,
+        }
+    }
+
+    fn join(&self, other: &Sign) -> Self {
+        if self == other { *self } else { Sign::Unknown }
+    }
+
+    fn meet(&self, other: &Sign) -> Self {
+        match (self, other) {
+            (Sign::Unknown, _) => *other,
+            _ => *self,
+        }
+    }
+
+    fn negate(&self) -> Self {
+        match self {
+            Sign::Negative => Sign::Positive,
+            Sign::Positive => Sign::Negative,
+            _ => *self,
+        }
+    }
+
+    fn add(&self, other: &Sign) -> Self {
+        match (self, other) {
+            (Sign::Zero, _) => *other,
+            (_, Sign::Zero) => *self,
+            _ => self.join(other),
+        }
+    }
+
+    fn multiply(&self, other: &Sign) -> Self {
+        match (self, other) {
+            (Sign::Zero, _) | (_, Sign::Zero) => Sign::Zero,
+            (Sign::Unknown, _) | (_, Sign::Unknown) => Sign::Unknown,
+            _ if self == other => Sign::Positive,
+            _ => Sign::Negative,
+        }
+    }
+
+    fn divide(&self, other: &Sign) -> Self {
+        match (self, other) {
+            (Sign::Zero, Sign::Negative | Sign::Positive) => Sign::Zero,
+            _ => Sign::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignDomain<'a> {
+    signs: BTreeMap<IdentifierId, Sign>,
+    finished: Option<Sign>,
+    returned: Option<Sign>,
+    summaries: &'a BTreeMap<IdentifierId, Sign>,
+}
+
+impl<'a> SignDomain<'a> {
+    pub fn new(params: Vec<IdentifierId>, summaries: &'a BTreeMap<IdentifierId, Sign>) -> Self {
+        Self {
+            signs: params
+                .into_iter()
+                .map(|iden| (iden, Sign::Unknown))
+                .collect(),
+            finished: None,
+            returned: None,
+            summaries,
+        }
+    }
+
+    pub fn returned(&self) -> Option<Sign> {
+        self.returned
+    }
+
+    fn join_returned(&self, other: &Self) -> Option<Sign> {
+        match (self.returned, other.returned) {
+            (Some(lhs), Some(rhs)) => Some(lhs.join(&rhs)),
+            (lhs, rhs) => lhs.or(rhs),
+        }
+    }
+
+    fn refine(&mut self, iden: IdentifierId, sign: Sign) {
+        let refined = self.get(iden).meet(&sign);
+        self.assign(iden, refined);
+    }
+
+    fn refine_compare(&self, iden: IdentifierId, cond: &ExpressionAST<'_>) -> (Self, Self) {
+        use ExpressionAST::*;
+        let mut true_ad = self.clone();
+        let mut false_ad = self.clone();
+        match cond {
+            EqualsEquals(_, rhs) | NotEquals(_, rhs) => {
+                let NumberLiteral(value) = rhs else {
+                    return (true_ad, false_ad);
+                };
+                if let EqualsEquals(..) = cond {
+                    true_ad.refine(iden, Sign::from_const(*value));
+                } else {
+                    false_ad.refine(iden, Sign::from_const(*value));
+                }
+            }
+            Greater(_, rhs) | GreaterEquals(_, rhs) => {
+                if let NumberLiteral(value) = rhs
+                    && (*value > 0 || (*value == 0 && matches!(cond, Greater(..))))
+                {
+                    true_ad.refine(iden, Sign::Positive);
+                }
+                if let NumberLiteral(value) = rhs
+                    && (*value < 0 || (*value == 0 && matches!(cond, GreaterEquals(..))))
+                {
+                    false_ad.refine(iden, Sign::Negative);
+                }
+            }
+            Less(_, rhs) | LessEquals(_, rhs) => {
+                if let NumberLiteral(value) = rhs
+                    && (*value < 0 || (*value == 0 && matches!(cond, Less(..))))
+                {
+                    true_ad.refine(iden, Sign::Negative);
+                }
+                if let NumberLiteral(value) = rhs
+                    && (*value > 0 || (*value == 0 && matches!(cond, LessEquals(..))))
+                {
+                    false_ad.refine(iden, Sign::Positive);
+                }
+            }
+            _ => {}
+        }
+        (true_ad, false_ad)
+    }
+}
+
+impl AbstractDomain for SignDomain<'_> {
+    type Value = Sign;
+
+    fn interp_expr(&self, expr: &ExpressionAST<'_>) -> Sign {
+        use ExpressionAST::*;
+        match expr {
+            NumberLiteral(value) => Sign::from_const(*value),
+            Variable(iden) => self.get(*iden),
+            Call(callee, _) => self.summaries.get(callee).copied().unwrap_or(Sign::Unknown),
+            Add(lhs, rhs) => self.interp_expr(lhs).add(&self.interp_expr(rhs)),
+            Subtract(lhs, rhs) => self.interp_expr(lhs).add(&self.interp_expr(rhs).negate()),
+            Multiply(lhs, rhs) => self.interp_expr(lhs).multiply(&self.interp_expr(rhs)),
+            Divide(lhs, rhs) => self.interp_expr(lhs).divide(&self.interp_expr(rhs)),
+            BlockExpr(block, value) => ai_block_expr(block, value, self),
+            _ => Sign::Unknown,
+        }
+    }
+
+    fn opaque_expr(&self, _expr: &ExpressionAST<'_>) -> Sign {
+        Sign::Unknown
+    }
+
+    fn get(&self, iden: IdentifierId) -> Sign {
+        self.signs[&iden]
+    }
+
+    fn assign(&mut self, iden: IdentifierId, val: Sign) {
+        self.signs.insert(iden, val);
+    }
+
+    fn branch(&self, cond: &ExpressionAST<'_>, _value: Sign) -> (Self, Self) {
+        use ExpressionAST::*;
+        match cond {
+            Variable(iden) => {
+                let mut false_ad = self.clone();
+                false_ad.refine(*iden, Sign::Zero);
+                (self.clone(), false_ad)
+            }
+            EqualsEquals(lhs, _)
+            | NotEquals(lhs, _)
+            | Less(lhs, _)
+            | LessEquals(lhs, _)
+            | Greater(lhs, _)
+            | GreaterEquals(lhs, _) => {
+                if let Variable(iden) = lhs {
+                    self.refine_compare(*iden, cond)
+                } else {
+                    (self.clone(), self.clone())
+                }
+            }
+            _ => (self.clone(), self.clone()),
+        }
+    }
+
+    fn finish_with(&mut self, val: Sign) {
+        self.finished = Some(val);
+        self.returned = Some(self.returned.map_or(val, |returned| returned.join(&val)));
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        let returned = self.join_returned(other);
+        if other.finished.is_some() {
+            return SignDomain {
+                returned,
+                ..self.clone()
+            };
+        } else if self.finished.is_some() {
+            return SignDomain {
+                returned,
+                ..other.clone()
+            };
+        }
+        let mut signs = BTreeMap::new();
+        for (self_iden, self_sign) in &self.signs {
+            if let Some(other_sign) = other.signs.get(self_iden) {
+                signs.insert(*self_iden, self_sign.join(other_sign));
+            }
+        }
+        SignDomain {
+            signs,
+            finished: None,
+            returned,
+            summaries: self.summaries,
+        }
+    }
+
+    fn widen(&self, other: &Self) -> (Self, bool) {
+        assert!(self.finished.is_none());
+        assert!(other.finished.is_none());
+        (self.join(other), false)
+    }
+}
+
+pub fn sign_summaries(
+    program: &ProgramAST<'_>,
+) -> Result<BTreeMap<IdentifierId, Sign>, IdentifierId> {
+    summarize(
+        program,
+        Sign::Unknown,
+        |func, summaries| {
+            let params = func.params.as_ref().to_vec();
+            let ad = SignDomain::new(params, summaries);
+            let mut ad = ai_block(&func.block, &ad)?;
+            if ad.finished.is_none() {
+                ad.finish_with(Sign::Unknown);
+            }
+            Ok(ad.returned().unwrap())
+        },
+        |old, new| old.join(new),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use util::arena::Arena;
+    use util::interner::StringInterner;
+
+    use crate::grammar::ProgramParser;
+
+    #[test]
+    fn sign_arithmetic() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let x = interner.intern("x");
+        let summaries = BTreeMap::new();
+        let mut ad = SignDomain::new(vec![x], &summaries);
+        ad.assign(x, Sign::Positive);
+
+        let var = ExpressionAST::Variable(x);
+        let one = ExpressionAST::NumberLiteral(1);
+        let minus_one = ExpressionAST::NumberLiteral(-1);
+        let zero = ExpressionAST::NumberLiteral(0);
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Add(&var, &one)),
+            Sign::Positive
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Add(&var, &zero)),
+            Sign::Positive
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Add(&var, &minus_one)),
+            Sign::Unknown
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Subtract(&minus_one, &var)),
+            Sign::Negative
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&var, &minus_one)),
+            Sign::Negative
+        );
+        assert_eq!(
+            ad.interp_expr(&ExpressionAST::Multiply(&var, &zero)),
+            Sign::Zero
+        );
+    }
+
+    #[test]
+    fn sign_branch() {
+        let mut buf: [u64; 1] = [0; 1];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let x = interner.intern("x");
+        let summaries = BTreeMap::new();
+        let ad = SignDomain::new(vec![x], &summaries);
+
+        let var = ExpressionAST::Variable(x);
+        let zero = ExpressionAST::NumberLiteral(0);
+        let cond = ExpressionAST::Greater(&var, &zero);
+        let (true_ad, false_ad) = ad.branch(&cond, ad.interp_expr(&cond));
+        assert_eq!(true_ad.get(x), Sign::Positive);
+        assert_eq!(false_ad.get(x), Sign::Unknown);
+
+        let cond = ExpressionAST::Less(&var, &zero);
+        let (true_ad, false_ad) = ad.branch(&cond, ad.interp_expr(&cond));
+        assert_eq!(true_ad.get(x), Sign::Negative);
+        assert_eq!(false_ad.get(x), Sign::Unknown);
+
+        let cond = ExpressionAST::EqualsEquals(&var, &zero);
+        let (true_ad, false_ad) = ad.branch(&cond, ad.interp_expr(&cond));
+        assert_eq!(true_ad.get(x), Sign::Zero);
+        assert_eq!(false_ad.get(x), Sign::Unknown);
+
+        let (true_ad, false_ad) = ad.branch(&var, ad.interp_expr(&var));
+        assert_eq!(true_ad.get(x), Sign::Unknown);
+        assert_eq!(false_ad.get(x), Sign::Zero);
+        assert_eq!(true_ad.join(&false_ad).get(x), Sign::Unknown);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn sign_calls() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn caller(x) { return neg(x) * neg(x); } fn neg(x) { return 0 - 3; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let summaries = sign_summaries(&program).unwrap();
+        assert_eq!(summaries[&interner.intern("neg")], Sign::Negative);
+        assert_eq!(summaries[&interner.intern("caller")], Sign::Positive);
+    }
+}
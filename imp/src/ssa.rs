@@ -1,17 +1,24 @@
 use core::cell::RefCell;
+use core::fmt;
+use core::fmt::Write;
 use core::hash::Hash;
-use core::mem::transmute;
-use std::collections::{BTreeMap, HashMap};
+use core::mem::{Discriminant, discriminant, replace};
+use core::ops::Range;
+use core::ptr;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use db::rebuild::{ENode, corebuild, rebuild_enode_table};
-use db::table::Table;
+use db::rebuild::{ENode, Rebuildable, corebuild, rebuild_table};
+use db::table::{MergePolicy, RowId, Table};
+use util::bitset::BitSet;
 use util::interner::{IdentifierId, StringInterner};
 use util::union_find::{ClassId, UnionFind};
 
-use crate::ai::AbstractDomain;
-use crate::ast::ExpressionAST;
+use crate::ai::{AbstractDomain, ai_block_expr};
+use crate::ast::{ExpressionAST, FunctionAST};
+use crate::interval::Interval;
+use crate::visit::{Visitor, walk_expr};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Term {
     Constant {
         value: i32,
@@ -59,6 +66,199 @@ pub enum Term {
         rhs: ClassId,
         root: ClassId,
     },
+
+    Subtract {
+        lhs: ClassId,
+        rhs: ClassId,
+        root: ClassId,
+    },
+    Multiply {
+        lhs: ClassId,
+        rhs: ClassId,
+        root: ClassId,
+    },
+    Divide {
+        lhs: ClassId,
+        rhs: ClassId,
+        root: ClassId,
+    },
+    Equals {
+        lhs: ClassId,
+        rhs: ClassId,
+        root: ClassId,
+    },
+    NotEquals {
+        lhs: ClassId,
+        rhs: ClassId,
+        root: ClassId,
+    },
+    Less {
+        lhs: ClassId,
+        rhs: ClassId,
+        root: ClassId,
+    },
+    LessEquals {
+        lhs: ClassId,
+        rhs: ClassId,
+        root: ClassId,
+    },
+
+    Opaque {
+        id: u32,
+        root: ClassId,
+    },
+}
+
+impl Term {
+    pub fn map_classes<F: FnMut(ClassId) -> ClassId>(&self, mut f: F) -> Self {
+        match self {
+            Term::Constant { value, root } => Term::Constant {
+                value: *value,
+                root: f(*root),
+            },
+            Term::Param { start, index, root } => Term::Param {
+                start: f(*start),
+                index: *index,
+                root: f(*root),
+            },
+            Term::Start { root } => Term::Start { root: f(*root) },
+            Term::Region { lhs, rhs, root } => Term::Region {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::Branch { pred, cond, root } => Term::Branch {
+                pred: f(*pred),
+                cond: f(*cond),
+                root: f(*root),
+            },
+            Term::ControlProj { pred, index, root } => Term::ControlProj {
+                pred: f(*pred),
+                index: *index,
+                root: f(*root),
+            },
+            Term::Finish { pred, value, root } => Term::Finish {
+                pred: f(*pred),
+                value: f(*value),
+                root: f(*root),
+            },
+            Term::Phi {
+                region,
+                lhs,
+                rhs,
+                root,
+            } => Term::Phi {
+                region: f(*region),
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::Add { lhs, rhs, root } => Term::Add {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::Subtract { lhs, rhs, root } => Term::Subtract {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::Multiply { lhs, rhs, root } => Term::Multiply {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::Divide { lhs, rhs, root } => Term::Divide {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::Equals { lhs, rhs, root } => Term::Equals {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::NotEquals { lhs, rhs, root } => Term::NotEquals {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::Less { lhs, rhs, root } => Term::Less {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::LessEquals { lhs, rhs, root } => Term::LessEquals {
+                lhs: f(*lhs),
+                rhs: f(*rhs),
+                root: f(*root),
+            },
+            Term::Opaque { id, root } => Term::Opaque {
+                id: *id,
+                root: f(*root),
+            },
+        }
+    }
+
+    pub fn operands(&self) -> Vec<ClassId> {
+        match self {
+            Term::Constant { .. } | Term::Start { .. } | Term::Opaque { .. } => vec![],
+            Term::Param { start, .. } => vec![*start],
+            Term::Region { lhs, rhs, .. }
+            | Term::Add { lhs, rhs, .. }
+            | Term::Subtract { lhs, rhs, .. }
+            | Term::Multiply { lhs, rhs, .. }
+            | Term::Divide { lhs, rhs, .. }
+            | Term::Equals { lhs, rhs, .. }
+            | Term::NotEquals { lhs, rhs, .. }
+            | Term::Less { lhs, rhs, .. }
+            | Term::LessEquals { lhs, rhs, .. } => vec![*lhs, *rhs],
+            Term::Branch { pred, cond, .. } => vec![*pred, *cond],
+            Term::ControlProj { pred, .. } => vec![*pred],
+            Term::Finish { pred, value, .. } => vec![*pred, *value],
+            Term::Phi {
+                region, lhs, rhs, ..
+            } => vec![*region, *lhs, *rhs],
+        }
+    }
+
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            Term::Start { .. }
+                | Term::Region { .. }
+                | Term::Branch { .. }
+                | Term::ControlProj { .. }
+                | Term::Finish { .. }
+        )
+    }
+
+    pub fn is_value(&self) -> bool {
+        !self.is_control()
+    }
+
+    pub fn structural_key(&self) -> (Discriminant<Term>, Vec<u32>) {
+        let det = match self {
+            Term::Constant { .. } => constant_encode(self).0.to_vec(),
+            Term::Param { .. } => param_encode(self).0.to_vec(),
+            Term::Start { .. } => start_encode(self).0.to_vec(),
+            Term::Region { .. } => region_encode(self).0.to_vec(),
+            Term::Branch { .. } => branch_encode(self).0.to_vec(),
+            Term::ControlProj { .. } => control_proj_encode(self).0.to_vec(),
+            Term::Finish { .. } => finish_encode(self).0.to_vec(),
+            Term::Phi { .. } => phi_encode(self).0.to_vec(),
+            Term::Add { .. } => add_encode(self).0.to_vec(),
+            Term::Subtract { .. } => subtract_encode(self).0.to_vec(),
+            Term::Multiply { .. } => multiply_encode(self).0.to_vec(),
+            Term::Divide { .. } => divide_encode(self).0.to_vec(),
+            Term::Equals { .. } => equals_encode(self).0.to_vec(),
+            Term::NotEquals { .. } => not_equals_encode(self).0.to_vec(),
+            Term::Less { .. } => less_encode(self).0.to_vec(),
+            Term::LessEquals { .. } => less_equals_encode(self).0.to_vec(),
+            Term::Opaque { .. } => opaque_encode(self).0.to_vec(),
+        };
+        (discriminant(self), det)
+    }
 }
 
 impl ENode for Term {
@@ -73,10 +273,22 @@ impl ENode for Term {
             Term::Finish { root, .. } => *root,
             Term::Phi { root, .. } => *root,
             Term::Add { root, .. } => *root,
+            Term::Subtract { root, .. } => *root,
+            Term::Multiply { root, .. } => *root,
+            Term::Divide { root, .. } => *root,
+            Term::Equals { root, .. } => *root,
+            Term::NotEquals { root, .. } => *root,
+            Term::Less { root, .. } => *root,
+            Term::LessEquals { root, .. } => *root,
+            Term::Opaque { root, .. } => *root,
         }
     }
 
-    fn canonicalize(&self, uf: &mut UnionFind) -> Self {
+    fn operands(&self) -> Vec<ClassId> {
+        Term::operands(self)
+    }
+
+    fn canonicalize(&self, uf: &UnionFind) -> Self {
         match self {
             Term::Constant { value, root } => Term::Constant {
                 value: *value,
@@ -126,55 +338,123 @@ impl ENode for Term {
                 rhs: uf.find(*rhs),
                 root: uf.find(*root),
             },
+            Term::Subtract { lhs, rhs, root } => Term::Subtract {
+                lhs: uf.find(*lhs),
+                rhs: uf.find(*rhs),
+                root: uf.find(*root),
+            },
+            Term::Multiply { lhs, rhs, root } => Term::Multiply {
+                lhs: uf.find(*lhs),
+                rhs: uf.find(*rhs),
+                root: uf.find(*root),
+            },
+            Term::Divide { lhs, rhs, root } => Term::Divide {
+                lhs: uf.find(*lhs),
+                rhs: uf.find(*rhs),
+                root: uf.find(*root),
+            },
+            Term::Equals { lhs, rhs, root } => Term::Equals {
+                lhs: uf.find(*lhs),
+                rhs: uf.find(*rhs),
+                root: uf.find(*root),
+            },
+            Term::NotEquals { lhs, rhs, root } => Term::NotEquals {
+                lhs: uf.find(*lhs),
+                rhs: uf.find(*rhs),
+                root: uf.find(*root),
+            },
+            Term::Less { lhs, rhs, root } => Term::Less {
+                lhs: uf.find(*lhs),
+                rhs: uf.find(*rhs),
+                root: uf.find(*root),
+            },
+            Term::LessEquals { lhs, rhs, root } => Term::LessEquals {
+                lhs: uf.find(*lhs),
+                rhs: uf.find(*rhs),
+                root: uf.find(*root),
+            },
+            Term::Opaque { id, root } => Term::Opaque {
+                id: *id,
+                root: uf.find(*root),
+            },
         }
     }
 }
 
+#[inline]
+fn class_to_u32(id: ClassId) -> u32 {
+    id.idx()
+}
+
+#[inline]
+fn u32_to_class(raw: u32) -> ClassId {
+    ClassId::new(raw)
+}
+
+#[inline]
+fn i32_to_u32(value: i32) -> u32 {
+    u32::from_ne_bytes(value.to_ne_bytes())
+}
+
+#[inline]
+fn u32_to_i32(raw: u32) -> i32 {
+    i32::from_ne_bytes(raw.to_ne_bytes())
+}
+
 fn constant_encode(term: &Term) -> ([u32; 1], [u32; 1]) {
     let Term::Constant { value, root } = term else {
         panic!()
     };
-    unsafe { transmute(([*value], [*root])) }
+    ([i32_to_u32(*value)], [class_to_u32(*root)])
 }
 
 fn param_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
     let Term::Param { start, index, root } = term else {
         panic!()
     };
-    unsafe { transmute(([*start, transmute(*index)], [*root])) }
+    ([class_to_u32(*start), *index], [class_to_u32(*root)])
 }
 
 fn start_encode(term: &Term) -> ([u32; 0], [u32; 1]) {
     let Term::Start { root } = term else { panic!() };
-    unsafe { transmute(*root) }
+    ([], [class_to_u32(*root)])
 }
 
 fn region_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
     let Term::Region { lhs, rhs, root } = term else {
         panic!()
     };
-    unsafe { transmute(([*lhs, *rhs], [*root])) }
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
 }
 
 fn branch_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
     let Term::Branch { pred, cond, root } = term else {
         panic!()
     };
-    unsafe { transmute(([*pred, *cond], [*root])) }
+    (
+        [class_to_u32(*pred), class_to_u32(*cond)],
+        [class_to_u32(*root)],
+    )
 }
 
 fn control_proj_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
     let Term::ControlProj { pred, index, root } = term else {
         panic!()
     };
-    unsafe { transmute(([*pred, transmute(*index)], [*root])) }
+    ([class_to_u32(*pred), *index], [class_to_u32(*root)])
 }
 
 fn finish_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
     let Term::Finish { pred, value, root } = term else {
         panic!()
     };
-    unsafe { transmute(([*pred, *value], [*root])) }
+    (
+        [class_to_u32(*pred), class_to_u32(*value)],
+        [class_to_u32(*root)],
+    )
 }
 
 fn phi_encode(term: &Term) -> ([u32; 3], [u32; 1]) {
@@ -187,104 +467,359 @@ fn phi_encode(term: &Term) -> ([u32; 3], [u32; 1]) {
     else {
         panic!()
     };
-    unsafe { transmute(([*region, *lhs, *rhs], [*root])) }
+    (
+        [
+            class_to_u32(*region),
+            class_to_u32(*lhs),
+            class_to_u32(*rhs),
+        ],
+        [class_to_u32(*root)],
+    )
 }
 
 fn add_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
     let Term::Add { lhs, rhs, root } = term else {
         panic!()
     };
-    unsafe { transmute(([*lhs, *rhs], [*root])) }
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
+}
+
+fn subtract_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
+    let Term::Subtract { lhs, rhs, root } = term else {
+        panic!()
+    };
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
+}
+
+fn multiply_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
+    let Term::Multiply { lhs, rhs, root } = term else {
+        panic!()
+    };
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
+}
+
+fn divide_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
+    let Term::Divide { lhs, rhs, root } = term else {
+        panic!()
+    };
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
+}
+
+fn equals_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
+    let Term::Equals { lhs, rhs, root } = term else {
+        panic!()
+    };
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
+}
+
+fn not_equals_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
+    let Term::NotEquals { lhs, rhs, root } = term else {
+        panic!()
+    };
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
+}
+
+fn less_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
+    let Term::Less { lhs, rhs, root } = term else {
+        panic!()
+    };
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
+}
+
+fn less_equals_encode(term: &Term) -> ([u32; 2], [u32; 1]) {
+    let Term::LessEquals { lhs, rhs, root } = term else {
+        panic!()
+    };
+    (
+        [class_to_u32(*lhs), class_to_u32(*rhs)],
+        [class_to_u32(*root)],
+    )
+}
+
+fn opaque_encode(term: &Term) -> ([u32; 1], [u32; 1]) {
+    let Term::Opaque { id, root } = term else {
+        panic!()
+    };
+    ([*id], [class_to_u32(*root)])
 }
 
 fn constant_decode(det: &[u32; 1], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::Constant {
-            #[allow(unnecessary_transmutes)]
-            value: transmute(det[0]),
-            root: transmute(dep[0]),
-        }
+    Term::Constant {
+        value: u32_to_i32(det[0]),
+        root: u32_to_class(dep[0]),
     }
 }
 
 fn param_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::Param {
-            start: transmute(det[0]),
-            index: transmute(det[1]),
-            root: transmute(dep[0]),
-        }
+    Term::Param {
+        start: u32_to_class(det[0]),
+        index: det[1],
+        root: u32_to_class(dep[0]),
     }
 }
 
 fn start_decode(_det: &[u32; 0], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::Start {
-            root: transmute(dep[0]),
-        }
+    Term::Start {
+        root: u32_to_class(dep[0]),
     }
 }
 
 fn region_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::Region {
-            lhs: transmute(det[0]),
-            rhs: transmute(det[1]),
-            root: transmute(dep[0]),
-        }
+    Term::Region {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
     }
 }
 
 fn branch_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::Branch {
-            pred: transmute(det[0]),
-            cond: transmute(det[1]),
-            root: transmute(dep[0]),
-        }
+    Term::Branch {
+        pred: u32_to_class(det[0]),
+        cond: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
     }
 }
 
 fn control_proj_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::ControlProj {
-            pred: transmute(det[0]),
-            index: transmute(det[1]),
-            root: transmute(dep[0]),
-        }
+    Term::ControlProj {
+        pred: u32_to_class(det[0]),
+        index: det[1],
+        root: u32_to_class(dep[0]),
     }
 }
+
 fn finish_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::Finish {
-            pred: transmute(det[0]),
-            value: transmute(det[1]),
-            root: transmute(dep[0]),
-        }
+    Term::Finish {
+        pred: u32_to_class(det[0]),
+        value: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
     }
 }
 
 fn phi_decode(det: &[u32; 3], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::Phi {
-            region: transmute(det[0]),
-            lhs: transmute(det[1]),
-            rhs: transmute(det[2]),
-            root: transmute(dep[0]),
-        }
+    Term::Phi {
+        region: u32_to_class(det[0]),
+        lhs: u32_to_class(det[1]),
+        rhs: u32_to_class(det[2]),
+        root: u32_to_class(dep[0]),
     }
 }
 
 fn add_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
-    unsafe {
-        Term::Add {
-            lhs: transmute(det[0]),
-            rhs: transmute(det[1]),
-            root: transmute(dep[0]),
+    Term::Add {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn subtract_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
+    Term::Subtract {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn multiply_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
+    Term::Multiply {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn divide_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
+    Term::Divide {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn equals_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
+    Term::Equals {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn not_equals_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
+    Term::NotEquals {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn less_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
+    Term::Less {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn less_equals_decode(det: &[u32; 2], dep: &[u32; 1]) -> Term {
+    Term::LessEquals {
+        lhs: u32_to_class(det[0]),
+        rhs: u32_to_class(det[1]),
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn opaque_decode(det: &[u32; 1], dep: &[u32; 1]) -> Term {
+    Term::Opaque {
+        id: det[0],
+        root: u32_to_class(dep[0]),
+    }
+}
+
+fn interval_encode(interval: &Interval) -> [u32; 2] {
+    let (low, high) = interval.bounds();
+    [i32_to_u32(low), i32_to_u32(high)]
+}
+
+fn interval_decode(raw: &[u32; 2]) -> Interval {
+    Interval::from_bounds(u32_to_i32(raw[0]), u32_to_i32(raw[1]))
+}
+
+fn interval_meet(lhs: &[u32; 2], rhs: &[u32; 2]) -> [u32; 2] {
+    interval_encode(&interval_decode(lhs).meet(&interval_decode(rhs)))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ClassInterval {
+    class: ClassId,
+    interval: Interval,
+}
+
+impl Rebuildable for ClassInterval {
+    fn canonical(&self, uf: &UnionFind) -> Self {
+        ClassInterval {
+            class: uf.find(self.class),
+            interval: self.interval,
+        }
+    }
+
+    fn solve(&self, old: Self, _uf: &mut UnionFind) -> Self {
+        ClassInterval {
+            class: old.class,
+            interval: self.interval.meet(&old.interval),
+        }
+    }
+}
+
+fn class_interval_encode(row: &ClassInterval) -> ([u32; 1], [u32; 2]) {
+    ([row.class.idx()], interval_encode(&row.interval))
+}
+
+fn class_interval_decode(det: &[u32; 1], dep: &[u32; 2]) -> ClassInterval {
+    ClassInterval {
+        class: ClassId::new(det[0]),
+        interval: interval_decode(dep),
+    }
+}
+
+fn hash_cons<const DET_COLS: usize, const DEP_COLS: usize>(
+    table: &mut Table<DET_COLS, DEP_COLS>,
+    uf: &UnionFind,
+    det: &[u32; DET_COLS],
+    dep: &[u32; DEP_COLS],
+) -> ([u32; DEP_COLS], RowId) {
+    match table.merge_policy() {
+        MergePolicy::Union => match table.try_insert_new_row(det, dep) {
+            Ok(row) => (*dep, row),
+            Err((row, old_dep)) => {
+                for (new, old) in dep.iter().zip(old_dep.iter()) {
+                    if new != old {
+                        uf.merge(ClassId::new(*new), ClassId::new(*old));
+                    }
+                }
+                (old_dep, row)
+            }
+        },
+        MergePolicy::Meet(meet) => {
+            let (row, dep) = table.insert_row_with_id(det, dep, meet);
+            (*dep, row)
+        }
+    }
+}
+
+fn replace_uses<const DET_COLS: usize>(
+    table: &mut Table<DET_COLS, 1>,
+    uf: &UnionFind,
+    old: ClassId,
+    new: ClassId,
+    encode: fn(&Term) -> ([u32; DET_COLS], [u32; 1]),
+    decode: fn(&[u32; DET_COLS], &[u32; 1]) -> Term,
+) {
+    let mut replaced = vec![];
+    let mut maybe_row_id = table.first_row();
+    while let Some(row_id) = maybe_row_id {
+        let row = table.get_row(row_id);
+        let term = decode(&row.0, &row.1);
+        let new_term = term.map_classes(|id| if id == old { new } else { id });
+        if term != new_term {
+            table.delete_row(row_id);
+            replaced.push(new_term);
         }
+        maybe_row_id = table.next_row(row_id);
     }
+    for term in replaced {
+        let (det, dep) = encode(&term);
+        hash_cons(table, uf, &det, &dep);
+    }
+}
+
+fn remove_terms<const DET_COLS: usize>(
+    table: &mut Table<DET_COLS, 1>,
+    uf: &UnionFind,
+    removed: &BTreeSet<Term>,
+    decode: fn(&[u32; DET_COLS], &[u32; 1]) -> Term,
+) {
+    table.retain(|det, dep| !removed.contains(&decode(det, dep).canonicalize(uf)));
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertError {
+    OutOfRange(ClassId),
+}
+
+fn operands_by_root(
+    table: &Table<2, 1>,
+    root: ClassId,
+) -> impl Iterator<Item = (ClassId, ClassId)> + '_ {
+    let key = i64::from(class_to_u32(root));
+    table.range_by_dep(0, key..key + 1).map(|row| {
+        let (det, _) = table.get_row(row);
+        (u32_to_class(det[0]), u32_to_class(det[1]))
+    })
 }
 
+#[derive(Clone)]
 pub struct Graph {
     constant: Table<1, 1>,
     param: Table<2, 1>,
@@ -295,14 +830,31 @@ pub struct Graph {
     finish: Table<2, 1>,
     phi: Table<3, 1>,
     add: Table<2, 1>,
+    subtract: Table<2, 1>,
+    multiply: Table<2, 1>,
+    divide: Table<2, 1>,
+    equals: Table<2, 1>,
+    not_equals: Table<2, 1>,
+    less: Table<2, 1>,
+    less_equals: Table<2, 1>,
+    opaque: Table<1, 1>,
+
+    interval: Table<1, 2>,
 
     uf: UnionFind,
+    returns: Vec<(ClassId, ClassId)>,
+    ret: Option<(ClassId, ClassId)>,
+    opaque_sites: HashMap<u64, ClassId>,
+    next_opaque: u32,
+    dirty: BTreeSet<ClassId>,
 }
 
+const STRUCTURAL_SEARCH_BUDGET: usize = 1 << 12;
+
 impl Graph {
     pub fn new(interner: &mut StringInterner) -> Self {
         Self {
-            constant: Table::new(interner.intern("cons")),
+            constant: Table::new_with_signed_columns(interner.intern("cons"), [true], [false]),
             param: Table::new(interner.intern("param")),
             start: Table::new(interner.intern("start")),
             region: Table::new(interner.intern("region")),
@@ -310,146 +862,668 @@ impl Graph {
             control_proj: Table::new(interner.intern("π")),
             finish: Table::new(interner.intern("finish")),
             phi: Table::new(interner.intern("ϕ")),
-            add: Table::new(interner.intern("+")),
+            add: Table::new_with_dep_index(interner.intern("+"), 0),
+            subtract: Table::new(interner.intern("-")),
+            multiply: Table::new_with_dep_index(interner.intern("*"), 0),
+            divide: Table::new(interner.intern("/")),
+            equals: Table::new(interner.intern("==")),
+            not_equals: Table::new(interner.intern("!=")),
+            less: Table::new(interner.intern("<")),
+            less_equals: Table::new(interner.intern("<=")),
+            opaque: Table::new(interner.intern("?")),
+
+            interval: Table::new_with_merge_policy(
+                interner.intern("interval"),
+                MergePolicy::Meet(interval_meet),
+            ),
 
             uf: UnionFind::new(),
+            returns: vec![],
+            ret: None,
+            opaque_sites: HashMap::new(),
+            next_opaque: 0,
+            dirty: BTreeSet::new(),
+        }
+    }
+
+    pub fn try_insert(&mut self, term: Term) -> Result<Term, InsertError> {
+        let num_classes = self.num_classes();
+        for id in term.operands().into_iter().chain([term.root()]) {
+            if !id.is_valid() || id.idx() >= num_classes {
+                return Err(InsertError::OutOfRange(id));
+            }
         }
+        Ok(self.insert(term))
     }
 
     pub fn insert(&mut self, term: Term) -> Term {
+        self.insert_get_id(term).0
+    }
+
+    pub fn insert_get_id(&mut self, term: Term) -> (Term, RowId) {
         match &term {
             Term::Constant { .. } => {
                 let (det, dep) = constant_encode(&term);
-                let new_dep = self
-                    .constant
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                constant_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.constant, &self.uf, &det, &dep);
+                (constant_decode(&det, &new_dep), row)
             }
             Term::Param { .. } => {
                 let (det, dep) = param_encode(&term);
-                let new_dep = self
-                    .param
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                param_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.param, &self.uf, &det, &dep);
+                (param_decode(&det, &new_dep), row)
             }
             Term::Start { .. } => {
                 let (det, dep) = start_encode(&term);
-                let new_dep = self
-                    .start
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                start_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.start, &self.uf, &det, &dep);
+                (start_decode(&det, &new_dep), row)
             }
             Term::Region { .. } => {
                 let (det, dep) = region_encode(&term);
-                let new_dep = self
-                    .region
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                region_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.region, &self.uf, &det, &dep);
+                (region_decode(&det, &new_dep), row)
             }
             Term::Branch { .. } => {
                 let (det, dep) = branch_encode(&term);
-                let new_dep = self
-                    .branch
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                branch_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.branch, &self.uf, &det, &dep);
+                (branch_decode(&det, &new_dep), row)
             }
             Term::ControlProj { .. } => {
                 let (det, dep) = control_proj_encode(&term);
-                let new_dep = self
-                    .control_proj
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                control_proj_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.control_proj, &self.uf, &det, &dep);
+                (control_proj_decode(&det, &new_dep), row)
             }
             Term::Finish { .. } => {
                 let (det, dep) = finish_encode(&term);
-                let new_dep = self
-                    .finish
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                finish_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.finish, &self.uf, &det, &dep);
+                (finish_decode(&det, &new_dep), row)
             }
             Term::Phi { .. } => {
                 let (det, dep) = phi_encode(&term);
-                let new_dep = self
-                    .phi
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                phi_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.phi, &self.uf, &det, &dep);
+                (phi_decode(&det, &new_dep), row)
             }
             Term::Add { .. } => {
                 let (det, dep) = add_encode(&term);
-                let new_dep = self
-                    .add
-                    .insert_row(&det, &dep, |new_dep, old_dep| {
-                        if new_dep != old_dep {
-                            unsafe { self.uf.merge(transmute(new_dep[0]), transmute(old_dep[0])) };
-                        }
-                        [old_dep[0]]
-                    })
-                    .clone();
-                add_decode(&det, &new_dep)
+                let (new_dep, row) = hash_cons(&mut self.add, &self.uf, &det, &dep);
+                (add_decode(&det, &new_dep), row)
             }
-        }
-    }
-
-    pub fn makeset(&mut self) -> ClassId {
-        self.uf.makeset()
+            Term::Subtract { .. } => {
+                let (det, dep) = subtract_encode(&term);
+                let (new_dep, row) = hash_cons(&mut self.subtract, &self.uf, &det, &dep);
+                (subtract_decode(&det, &new_dep), row)
+            }
+            Term::Multiply { .. } => {
+                let (det, dep) = multiply_encode(&term);
+                let (new_dep, row) = hash_cons(&mut self.multiply, &self.uf, &det, &dep);
+                (multiply_decode(&det, &new_dep), row)
+            }
+            Term::Divide { .. } => {
+                let (det, dep) = divide_encode(&term);
+                let (new_dep, row) = hash_cons(&mut self.divide, &self.uf, &det, &dep);
+                (divide_decode(&det, &new_dep), row)
+            }
+            Term::Equals { .. } => {
+                let (det, dep) = equals_encode(&term);
+                let (new_dep, row) = hash_cons(&mut self.equals, &self.uf, &det, &dep);
+                (equals_decode(&det, &new_dep), row)
+            }
+            Term::NotEquals { .. } => {
+                let (det, dep) = not_equals_encode(&term);
+                let (new_dep, row) = hash_cons(&mut self.not_equals, &self.uf, &det, &dep);
+                (not_equals_decode(&det, &new_dep), row)
+            }
+            Term::Less { .. } => {
+                let (det, dep) = less_encode(&term);
+                let (new_dep, row) = hash_cons(&mut self.less, &self.uf, &det, &dep);
+                (less_decode(&det, &new_dep), row)
+            }
+            Term::LessEquals { .. } => {
+                let (det, dep) = less_equals_encode(&term);
+                let (new_dep, row) = hash_cons(&mut self.less_equals, &self.uf, &det, &dep);
+                (less_equals_decode(&det, &new_dep), row)
+            }
+            Term::Opaque { .. } => {
+                let (det, dep) = opaque_encode(&term);
+                let (new_dep, row) = hash_cons(&mut self.opaque, &self.uf, &det, &dep);
+                (opaque_decode(&det, &new_dep), row)
+            }
+        }
+    }
+
+    pub fn num_enodes(&self) -> usize {
+        (self.constant.num_allocated_rows
+            + self.param.num_allocated_rows
+            + self.start.num_allocated_rows
+            + self.region.num_allocated_rows
+            + self.branch.num_allocated_rows
+            + self.control_proj.num_allocated_rows
+            + self.finish.num_allocated_rows
+            + self.phi.num_allocated_rows
+            + self.add.num_allocated_rows
+            + self.subtract.num_allocated_rows
+            + self.multiply.num_allocated_rows
+            + self.divide.num_allocated_rows
+            + self.equals.num_allocated_rows
+            + self.not_equals.num_allocated_rows
+            + self.less.num_allocated_rows
+            + self.less_equals.num_allocated_rows
+            + self.opaque.num_allocated_rows) as usize
+    }
+
+    pub fn constant_value(&self, id: ClassId) -> Option<i32> {
+        let id = self.find(id);
+        self.constant
+            .iter()
+            .map(|row| constant_decode(&row.0, &row.1))
+            .find_map(|term| match term {
+                Term::Constant { value, root } if self.find(root) == id => Some(value),
+                _ => None,
+            })
+    }
+
+    pub fn makeset(&mut self) -> ClassId {
+        self.uf.makeset()
+    }
+
+    pub fn makeset_many(&mut self, n: u32) -> Range<u32> {
+        self.uf.makeset_many(n)
+    }
+
+    pub fn num_classes(&self) -> u32 {
+        self.uf.num_classes()
+    }
+
+    pub fn union_find(&self) -> &UnionFind {
+        &self.uf
     }
 
     pub fn find(&self, id: ClassId) -> ClassId {
         self.uf.find(id)
     }
 
+    pub fn merge_mut(&mut self, a: ClassId, b: ClassId) -> (ClassId, bool) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a == b {
+            return (a, false);
+        }
+        self.dirty.insert(a);
+        self.dirty.insert(b);
+        (self.uf.merge(a, b), true)
+    }
+
+    pub fn merge_all(&mut self, ids: &[ClassId]) -> Option<ClassId> {
+        let reps: BTreeSet<ClassId> = ids.iter().map(|id| self.find(*id)).collect();
+        if reps.len() > 1 {
+            self.dirty.extend(reps);
+        }
+        self.uf.merge_all(ids)
+    }
+
+    pub fn replace_all_uses(&mut self, old: ClassId, new: ClassId) -> ClassId {
+        let uf = &self.uf;
+        replace_uses(
+            &mut self.constant,
+            uf,
+            old,
+            new,
+            constant_encode,
+            constant_decode,
+        );
+        replace_uses(&mut self.param, uf, old, new, param_encode, param_decode);
+        replace_uses(&mut self.start, uf, old, new, start_encode, start_decode);
+        replace_uses(&mut self.region, uf, old, new, region_encode, region_decode);
+        replace_uses(&mut self.branch, uf, old, new, branch_encode, branch_decode);
+        replace_uses(
+            &mut self.control_proj,
+            uf,
+            old,
+            new,
+            control_proj_encode,
+            control_proj_decode,
+        );
+        replace_uses(&mut self.finish, uf, old, new, finish_encode, finish_decode);
+        replace_uses(&mut self.phi, uf, old, new, phi_encode, phi_decode);
+        replace_uses(&mut self.add, uf, old, new, add_encode, add_decode);
+        replace_uses(
+            &mut self.subtract,
+            uf,
+            old,
+            new,
+            subtract_encode,
+            subtract_decode,
+        );
+        replace_uses(
+            &mut self.multiply,
+            uf,
+            old,
+            new,
+            multiply_encode,
+            multiply_decode,
+        );
+        replace_uses(&mut self.divide, uf, old, new, divide_encode, divide_decode);
+        replace_uses(&mut self.equals, uf, old, new, equals_encode, equals_decode);
+        replace_uses(
+            &mut self.not_equals,
+            uf,
+            old,
+            new,
+            not_equals_encode,
+            not_equals_decode,
+        );
+        replace_uses(&mut self.less, uf, old, new, less_encode, less_decode);
+        replace_uses(
+            &mut self.less_equals,
+            uf,
+            old,
+            new,
+            less_equals_encode,
+            less_equals_decode,
+        );
+        replace_uses(&mut self.opaque, uf, old, new, opaque_encode, opaque_decode);
+        self.merge_mut(old, new).0
+    }
+
+    pub fn absorb(&mut self, other: Graph) -> impl Fn(ClassId) -> ClassId + use<> {
+        let offset = self.makeset_many(other.num_classes()).start;
+        let remap = move |id: ClassId| ClassId::new(id.idx() + offset);
+        for (id, rep) in other.uf.iter() {
+            if id != rep {
+                self.merge_mut(remap(id), remap(rep));
+            }
+        }
+        let opaque_offset = self.next_opaque;
+        self.next_opaque += other.next_opaque;
+        for term in other.terms() {
+            let term = match term.map_classes(remap) {
+                Term::Opaque { id, root } => Term::Opaque {
+                    id: id + opaque_offset,
+                    root,
+                },
+                term => term,
+            };
+            self.insert(term);
+        }
+        for (det, dep) in other.interval.iter() {
+            let det = [remap(ClassId::new(det[0])).idx()];
+            hash_cons(&mut self.interval, &self.uf, &det, &dep);
+        }
+        // Site keys are `func_idx << 32 | preorder`, so shifting the function index past every one
+        // of ours keeps imported sites distinct, just like the opaque ids above.
+        let site_offset = self
+            .opaque_sites
+            .keys()
+            .map(|site| (site >> 32) + 1)
+            .max()
+            .unwrap_or(0);
+        for (site, root) in other.opaque_sites {
+            self.opaque_sites
+                .insert(site + (site_offset << 32), remap(root));
+        }
+        self.returns.extend(
+            other
+                .returns
+                .into_iter()
+                .map(|(pred, value)| (remap(pred), remap(value))),
+        );
+        if self.ret.is_none() {
+            self.ret = other.ret.map(|(pred, value)| (remap(pred), remap(value)));
+        }
+        remap
+    }
+
+    pub fn live_classes(&self) -> BitSet {
+        let mut live = BitSet::with_capacity(self.num_classes() as usize);
+        for term in self.canonical_terms() {
+            live.insert(term.root().idx() as usize);
+            live.extend(term.operands().into_iter().map(|id| id.idx() as usize));
+        }
+        for (pred, value) in self.return_sites() {
+            live.insert(pred.idx() as usize);
+            live.insert(value.idx() as usize);
+        }
+        if let Some((pred, value)) = self.ret {
+            live.insert(self.find(pred).idx() as usize);
+            live.insert(self.find(value).idx() as usize);
+        }
+        live.extend(
+            self.opaque_sites
+                .values()
+                .map(|id| self.find(*id).idx() as usize),
+        );
+        live
+    }
+
+    pub fn dead_code_classes(&self) -> Vec<ClassId> {
+        let mut classes: BTreeMap<ClassId, Vec<Term>> = BTreeMap::new();
+        for term in self.canonical_terms() {
+            classes.entry(term.root()).or_default().push(term);
+        }
+        let mut worklist: Vec<ClassId> = self
+            .return_sites()
+            .flat_map(|(pred, value)| [pred, value])
+            .collect();
+        worklist.extend(
+            classes
+                .values()
+                .flatten()
+                .filter(|term| matches!(term, Term::Finish { .. }))
+                .map(|term| term.root()),
+        );
+        let mut reachable = BitSet::with_capacity(self.num_classes() as usize);
+        while let Some(class) = worklist.pop() {
+            if !reachable.insert(class.idx() as usize) {
+                continue;
+            }
+            for term in classes.get(&class).map_or(&[][..], |terms| terms) {
+                worklist.extend(term.operands());
+            }
+        }
+        self.live_classes()
+            .iter()
+            .filter(|idx| !reachable.contains(*idx))
+            .map(|idx| ClassId::new(idx as u32))
+            .collect()
+    }
+
+    pub fn simplify_control(&mut self) -> bool {
+        let mut ever_changed = false;
+        let mut dead = BTreeSet::new();
+        loop {
+            let terms: Vec<Term> = self.canonical_terms().collect();
+            let mut removed = BTreeSet::new();
+            let mut merges = vec![];
+
+            dead = dead.into_iter().map(|class| self.find(class)).collect();
+            for term in &terms {
+                let Term::Branch { pred, cond, root } = *term else {
+                    continue;
+                };
+                let Some(cond) = self.constant_value(cond) else {
+                    continue;
+                };
+                let taken = (cond != 0) as u32;
+                removed.insert(*term);
+                for proj in &terms {
+                    if let Term::ControlProj {
+                        pred: proj_pred,
+                        index,
+                        root: proj_root,
+                    } = *proj
+                        && proj_pred == root
+                    {
+                        removed.insert(*proj);
+                        if index == taken {
+                            merges.push((proj_root, pred));
+                        } else {
+                            dead.insert(proj_root);
+                        }
+                    }
+                }
+            }
+
+            // Control only flows forward out of a dead class, so everything it reaches is dead
+            // too, except for regions that still have a live predecessor.
+            loop {
+                let mut changed = false;
+                for term in &terms {
+                    let is_dead = match *term {
+                        Term::Branch { pred, .. } | Term::ControlProj { pred, .. } => {
+                            dead.contains(&pred)
+                        }
+                        Term::Region { lhs, rhs, .. } => dead.contains(&lhs) && dead.contains(&rhs),
+                        _ => false,
+                    };
+                    if is_dead {
+                        changed |= dead.insert(term.root());
+                    }
+                }
+                if !changed {
+                    break;
+                }
+            }
+
+            for term in &terms {
+                let Term::Region { lhs, rhs, root } = *term else {
+                    continue;
+                };
+                let kept_lhs = if dead.contains(&rhs) {
+                    true
+                } else if dead.contains(&lhs) {
+                    false
+                } else if lhs == rhs {
+                    true
+                } else {
+                    continue;
+                };
+                let phis: Vec<_> = terms
+                    .iter()
+                    .filter_map(|phi| match *phi {
+                        Term::Phi {
+                            region,
+                            lhs: phi_lhs,
+                            rhs: phi_rhs,
+                            root: phi_root,
+                        } if region == root => Some((*phi, phi_lhs, phi_rhs, phi_root)),
+                        _ => None,
+                    })
+                    .collect();
+                // With both edges live, a phi choosing between distinct values still needs the
+                // region to tell them apart.
+                if !dead.contains(&lhs)
+                    && !dead.contains(&rhs)
+                    && phis
+                        .iter()
+                        .any(|(_, phi_lhs, phi_rhs, _)| phi_lhs != phi_rhs)
+                {
+                    continue;
+                }
+                removed.insert(*term);
+                merges.push((root, if kept_lhs { lhs } else { rhs }));
+                for (phi, phi_lhs, phi_rhs, phi_root) in phis {
+                    removed.insert(phi);
+                    merges.push((phi_root, if kept_lhs { phi_lhs } else { phi_rhs }));
+                }
+            }
+
+            if removed.is_empty() {
+                break;
+            }
+            ever_changed = true;
+            remove_terms(&mut self.branch, &self.uf, &removed, branch_decode);
+            remove_terms(
+                &mut self.control_proj,
+                &self.uf,
+                &removed,
+                control_proj_decode,
+            );
+            remove_terms(&mut self.region, &self.uf, &removed, region_decode);
+            remove_terms(&mut self.phi, &self.uf, &removed, phi_decode);
+            for (a, b) in merges {
+                self.merge_mut(a, b);
+            }
+            self.rebuild();
+        }
+        ever_changed
+    }
+
+    pub fn renumber_dense(&mut self) -> impl Fn(ClassId) -> ClassId + use<> {
+        let terms: Vec<Term> = self.canonical_terms().collect();
+        let live = self.live_classes();
+
+        let intervals: Vec<_> = self.interval.iter().collect();
+        let policy = self.uf.policy();
+        let old_uf = replace(&mut self.uf, UnionFind::new_with_policy(policy));
+        let dense: BTreeMap<ClassId, ClassId> = live
+            .iter()
+            .map(|idx| (ClassId::new(idx as u32), self.uf.makeset()))
+            .collect();
+        let remap = move |id: ClassId| {
+            dense
+                .get(&old_uf.find(id))
+                .copied()
+                .unwrap_or(ClassId::INVALID)
+        };
+
+        self.constant.clear();
+        self.param.clear();
+        self.start.clear();
+        self.region.clear();
+        self.branch.clear();
+        self.control_proj.clear();
+        self.finish.clear();
+        self.phi.clear();
+        self.add.clear();
+        self.subtract.clear();
+        self.multiply.clear();
+        self.divide.clear();
+        self.equals.clear();
+        self.not_equals.clear();
+        self.less.clear();
+        self.less_equals.clear();
+        self.opaque.clear();
+        self.interval.clear();
+        for term in terms {
+            self.insert(term.map_classes(&remap));
+        }
+        for (det, dep) in intervals {
+            let id = remap(ClassId::new(det[0]));
+            if id.is_valid() {
+                hash_cons(&mut self.interval, &self.uf, &[id.idx()], &dep);
+            }
+        }
+        for (pred, value) in &mut self.returns {
+            *pred = remap(*pred);
+            *value = remap(*value);
+        }
+        self.ret = self.ret.map(|(pred, value)| (remap(pred), remap(value)));
+        for id in self.opaque_sites.values_mut() {
+            *id = remap(*id);
+        }
+        self.dirty.clear();
+        remap
+    }
+
+    #[deprecated(note = "use `merge_mut`, which records dirtied classes")]
     pub fn merge(&self, a: ClassId, b: ClassId) -> ClassId {
         self.uf.merge(a, b)
     }
 
+    pub fn dirty_classes(&self) -> impl Iterator<Item = ClassId> + '_ {
+        self.dirty.iter().copied()
+    }
+
+    pub fn find_term(&self, term: Term) -> Option<Term> {
+        let term = term.canonicalize(&self.uf);
+        match term {
+            Term::Constant { .. } => {
+                let det = constant_encode(&term).0;
+                self.constant
+                    .map(&det)
+                    .map(|dep| constant_decode(&det, dep))
+            }
+            Term::Param { .. } => {
+                let det = param_encode(&term).0;
+                self.param.map(&det).map(|dep| param_decode(&det, dep))
+            }
+            Term::Start { .. } => {
+                let det = start_encode(&term).0;
+                self.start.map(&det).map(|dep| start_decode(&det, dep))
+            }
+            Term::Region { .. } => {
+                let det = region_encode(&term).0;
+                self.region.map(&det).map(|dep| region_decode(&det, dep))
+            }
+            Term::Branch { .. } => {
+                let det = branch_encode(&term).0;
+                self.branch.map(&det).map(|dep| branch_decode(&det, dep))
+            }
+            Term::ControlProj { .. } => {
+                let det = control_proj_encode(&term).0;
+                self.control_proj
+                    .map(&det)
+                    .map(|dep| control_proj_decode(&det, dep))
+            }
+            Term::Finish { .. } => {
+                let det = finish_encode(&term).0;
+                self.finish.map(&det).map(|dep| finish_decode(&det, dep))
+            }
+            Term::Phi { .. } => {
+                let det = phi_encode(&term).0;
+                self.phi.map(&det).map(|dep| phi_decode(&det, dep))
+            }
+            Term::Add { .. } => {
+                let det = add_encode(&term).0;
+                self.add.map(&det).map(|dep| add_decode(&det, dep))
+            }
+            Term::Subtract { .. } => {
+                let det = subtract_encode(&term).0;
+                self.subtract
+                    .map(&det)
+                    .map(|dep| subtract_decode(&det, dep))
+            }
+            Term::Multiply { .. } => {
+                let det = multiply_encode(&term).0;
+                self.multiply
+                    .map(&det)
+                    .map(|dep| multiply_decode(&det, dep))
+            }
+            Term::Divide { .. } => {
+                let det = divide_encode(&term).0;
+                self.divide.map(&det).map(|dep| divide_decode(&det, dep))
+            }
+            Term::Equals { .. } => {
+                let det = equals_encode(&term).0;
+                self.equals.map(&det).map(|dep| equals_decode(&det, dep))
+            }
+            Term::NotEquals { .. } => {
+                let det = not_equals_encode(&term).0;
+                self.not_equals
+                    .map(&det)
+                    .map(|dep| not_equals_decode(&det, dep))
+            }
+            Term::Less { .. } => {
+                let det = less_encode(&term).0;
+                self.less.map(&det).map(|dep| less_decode(&det, dep))
+            }
+            Term::LessEquals { .. } => {
+                let det = less_equals_encode(&term).0;
+                self.less_equals
+                    .map(&det)
+                    .map(|dep| less_equals_decode(&det, dep))
+            }
+            Term::Opaque { .. } => {
+                let det = opaque_encode(&term).0;
+                self.opaque.map(&det).map(|dep| opaque_decode(&det, dep))
+            }
+        }
+    }
+
+    pub fn contains(&self, term: Term) -> bool {
+        self.find_term(term).is_some()
+    }
+
+    pub fn equivalent_terms(&self, term: Term) -> Vec<Term> {
+        let Some(found) = self.find_term(term) else {
+            return vec![];
+        };
+        let root = self.find(found.root());
+        self.canonical_terms()
+            .filter(|term| term.root() == root)
+            .collect()
+    }
+
+    /// The operands of each `+` term in `id`'s class, looked up through the table's root
+    /// index. Rows are keyed by their stored root, so this is complete once the graph is rebuilt.
+    pub fn add_operands(&self, id: ClassId) -> impl Iterator<Item = (ClassId, ClassId)> + '_ {
+        operands_by_root(&self.add, self.find(id))
+    }
+
+    /// Like [`Graph::add_operands`], for `*` terms.
+    pub fn multiply_operands(&self, id: ClassId) -> impl Iterator<Item = (ClassId, ClassId)> + '_ {
+        operands_by_root(&self.multiply, self.find(id))
+    }
+
     pub fn terms(&self) -> impl Iterator<Item = Term> + '_ {
         self.constant
             .iter()
@@ -466,50 +1540,387 @@ impl Graph {
             .chain(self.finish.iter().map(|row| finish_decode(&row.0, &row.1)))
             .chain(self.phi.iter().map(|row| phi_decode(&row.0, &row.1)))
             .chain(self.add.iter().map(|row| add_decode(&row.0, &row.1)))
+            .chain(
+                self.subtract
+                    .iter()
+                    .map(|row| subtract_decode(&row.0, &row.1)),
+            )
+            .chain(
+                self.multiply
+                    .iter()
+                    .map(|row| multiply_decode(&row.0, &row.1)),
+            )
+            .chain(self.divide.iter().map(|row| divide_decode(&row.0, &row.1)))
+            .chain(self.equals.iter().map(|row| equals_decode(&row.0, &row.1)))
+            .chain(
+                self.not_equals
+                    .iter()
+                    .map(|row| not_equals_decode(&row.0, &row.1)),
+            )
+            .chain(self.less.iter().map(|row| less_decode(&row.0, &row.1)))
+            .chain(
+                self.less_equals
+                    .iter()
+                    .map(|row| less_equals_decode(&row.0, &row.1)),
+            )
+            .chain(self.opaque.iter().map(|row| opaque_decode(&row.0, &row.1)))
+    }
+
+    pub fn canonical_terms(&self) -> impl Iterator<Item = Term> + '_ {
+        self.terms()
+            .map(|term| term.map_classes(|id| self.find(id)))
+    }
+
+    /// Loop heads built by `While` take the loop entry as `lhs` and the back-edge as `rhs`, so a
+    /// region's `rhs` is a back-edge exactly when control flows from the region around to it.
+    pub fn back_edge(&self, region: ClassId) -> Option<ClassId> {
+        let region = self.find(region);
+        let mut classes: BTreeMap<ClassId, Vec<Term>> = BTreeMap::new();
+        for term in self.canonical_terms() {
+            classes.entry(term.root()).or_default().push(term);
+        }
+        let reaches_region = |back_edge: ClassId| {
+            let mut worklist = vec![back_edge];
+            let mut visited = BitSet::with_capacity(self.num_classes() as usize);
+            while let Some(class) = worklist.pop() {
+                if class == region {
+                    return true;
+                }
+                if !visited.insert(class.idx() as usize) {
+                    continue;
+                }
+                for term in classes.get(&class).map_or(&[][..], |terms| terms) {
+                    match *term {
+                        Term::Branch { pred, .. } | Term::ControlProj { pred, .. } => {
+                            worklist.push(pred)
+                        }
+                        Term::Region { lhs, rhs, .. } => worklist.extend([lhs, rhs]),
+                        _ => {}
+                    }
+                }
+            }
+            false
+        };
+        classes.get(&region)?.iter().find_map(|term| match *term {
+            Term::Region { rhs, .. } if reaches_region(rhs) => Some(rhs),
+            _ => None,
+        })
+    }
+
+    pub fn topo_terms(&self) -> Result<Vec<Term>, ClassId> {
+        fn visit(
+            class: ClassId,
+            classes: &BTreeMap<ClassId, Vec<Term>>,
+            visiting: &mut BitSet,
+            done: &mut BitSet,
+            order: &mut Vec<Term>,
+        ) -> Result<(), ClassId> {
+            let idx = class.idx() as usize;
+            if done.contains(idx) {
+                return Ok(());
+            }
+            if !visiting.insert(idx) {
+                return Err(class);
+            }
+            let terms = classes.get(&class).map_or(&[][..], |terms| terms);
+            for term in terms {
+                if matches!(term, Term::Phi { .. } | Term::Region { .. }) {
+                    continue;
+                }
+                for operand in term.operands() {
+                    visit(operand, classes, visiting, done, order)?;
+                }
+            }
+            done.insert(idx);
+            order.extend(terms);
+            Ok(())
+        }
+
+        let mut classes: BTreeMap<ClassId, Vec<Term>> = BTreeMap::new();
+        for term in self.canonical_terms() {
+            classes.entry(term.root()).or_default().push(term);
+        }
+        let mut visiting = BitSet::with_capacity(self.num_classes() as usize);
+        let mut done = BitSet::with_capacity(self.num_classes() as usize);
+        let mut order = vec![];
+        for class in classes.keys() {
+            visit(*class, &classes, &mut visiting, &mut done, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Phis and regions are both binary, so their arities agree by construction. What can go
+    /// wrong is a phi whose region operand isn't a region, or whose inputs are control rather
+    /// than values.
+    pub fn check_phi_regions(&self) -> Result<(), String> {
+        let mut regions = BTreeSet::new();
+        let mut control = BTreeSet::new();
+        for term in self.canonical_terms() {
+            if matches!(term, Term::Region { .. }) {
+                regions.insert(term.root());
+            }
+            if term.is_control() {
+                control.insert(term.root());
+            }
+        }
+        for term in self.canonical_terms() {
+            let Term::Phi {
+                region,
+                lhs,
+                rhs,
+                root,
+            } = term
+            else {
+                continue;
+            };
+            if !regions.contains(&region) {
+                return Err(format!(
+                    "phi {} has region operand {} which is not a region",
+                    root.idx(),
+                    region.idx()
+                ));
+            }
+            if let Some(input) = [lhs, rhs].into_iter().find(|id| control.contains(id)) {
+                return Err(format!(
+                    "phi {} has input {} which is control, not a value",
+                    root.idx(),
+                    input.idx()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_control_uniqueness(&self) -> Result<(), String> {
+        let mut starts = BTreeSet::new();
+        let mut finishes = BTreeSet::new();
+        for term in self.canonical_terms() {
+            match term {
+                Term::Start { root } => {
+                    starts.insert(root);
+                }
+                Term::Param { start, .. } => {
+                    starts.insert(start);
+                }
+                Term::Finish { root, .. } => {
+                    finishes.insert(root);
+                }
+                _ => {}
+            }
+        }
+        if starts.len() != 1 {
+            return Err(format!("expected one start class, found {}", starts.len()));
+        }
+        if finishes.len() > 1 {
+            return Err(format!(
+                "expected at most one finish class, found {}",
+                finishes.len()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn iter_edges(&self) -> impl Iterator<Item = (Term, usize, ClassId)> + '_ {
+        self.terms().flat_map(|term| {
+            term.operands()
+                .into_iter()
+                .enumerate()
+                .map(move |(idx, operand)| (term, idx, operand))
+        })
+    }
+
+    pub fn table_rows(&self, symbol: IdentifierId) -> Option<Vec<(Vec<u32>, Vec<u32>)>> {
+        fn rows<const DET_COLS: usize, const DEP_COLS: usize>(
+            table: &Table<DET_COLS, DEP_COLS>,
+        ) -> Vec<(Vec<u32>, Vec<u32>)> {
+            table
+                .iter()
+                .map(|(det, dep)| (det.to_vec(), dep.to_vec()))
+                .collect()
+        }
+
+        if symbol == self.constant.symbol {
+            Some(rows(&self.constant))
+        } else if symbol == self.param.symbol {
+            Some(rows(&self.param))
+        } else if symbol == self.start.symbol {
+            Some(rows(&self.start))
+        } else if symbol == self.region.symbol {
+            Some(rows(&self.region))
+        } else if symbol == self.branch.symbol {
+            Some(rows(&self.branch))
+        } else if symbol == self.control_proj.symbol {
+            Some(rows(&self.control_proj))
+        } else if symbol == self.finish.symbol {
+            Some(rows(&self.finish))
+        } else if symbol == self.phi.symbol {
+            Some(rows(&self.phi))
+        } else if symbol == self.add.symbol {
+            Some(rows(&self.add))
+        } else if symbol == self.subtract.symbol {
+            Some(rows(&self.subtract))
+        } else if symbol == self.multiply.symbol {
+            Some(rows(&self.multiply))
+        } else if symbol == self.divide.symbol {
+            Some(rows(&self.divide))
+        } else if symbol == self.equals.symbol {
+            Some(rows(&self.equals))
+        } else if symbol == self.not_equals.symbol {
+            Some(rows(&self.not_equals))
+        } else if symbol == self.less.symbol {
+            Some(rows(&self.less))
+        } else if symbol == self.less_equals.symbol {
+            Some(rows(&self.less_equals))
+        } else if symbol == self.opaque.symbol {
+            Some(rows(&self.opaque))
+        } else if symbol == self.interval.symbol {
+            Some(rows(&self.interval))
+        } else {
+            None
+        }
     }
 
     pub fn constant(&mut self, value: i32) -> ClassId {
         let root = self.makeset();
         self.insert(Term::Constant { value, root });
-        let root = self.find(root);
-        root
+        self.find(root)
     }
 
     pub fn add(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
         let root = self.makeset();
         self.insert(Term::Add { lhs, rhs, root });
-        let root = self.find(root);
-        root
+        self.find(root)
+    }
+
+    pub fn subtract(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
+        let root = self.makeset();
+        self.insert(Term::Subtract { lhs, rhs, root });
+        self.find(root)
+    }
+
+    pub fn multiply(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
+        let root = self.makeset();
+        self.insert(Term::Multiply { lhs, rhs, root });
+        self.find(root)
+    }
+
+    pub fn divide(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
+        let root = self.makeset();
+        self.insert(Term::Divide { lhs, rhs, root });
+        self.find(root)
+    }
+
+    pub fn equals(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
+        let root = self.makeset();
+        self.insert(Term::Equals { lhs, rhs, root });
+        self.find(root)
+    }
+
+    pub fn not_equals(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
+        let root = self.makeset();
+        self.insert(Term::NotEquals { lhs, rhs, root });
+        self.find(root)
+    }
+
+    pub fn less(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
+        let root = self.makeset();
+        self.insert(Term::Less { lhs, rhs, root });
+        self.find(root)
+    }
+
+    pub fn less_equals(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
+        let root = self.makeset();
+        self.insert(Term::LessEquals { lhs, rhs, root });
+        self.find(root)
     }
 
     pub fn region(&mut self, lhs: ClassId, rhs: ClassId) -> ClassId {
         let root = self.makeset();
         self.insert(Term::Region { lhs, rhs, root });
-        let root = self.find(root);
-        root
+        self.find(root)
     }
 
     pub fn branch(&mut self, pred: ClassId, cond: ClassId) -> ClassId {
         let root = self.makeset();
         self.insert(Term::Branch { pred, cond, root });
-        let root = self.find(root);
-        root
+        self.find(root)
     }
 
     pub fn control_proj(&mut self, pred: ClassId, index: u32) -> ClassId {
         let root = self.makeset();
         self.insert(Term::ControlProj { pred, index, root });
-        let root = self.find(root);
-        root
+        self.find(root)
     }
 
+    /// Records a return from `pred` with `value` and joins it with the earlier returns through a
+    /// region and, where the values differ, a phi. The graph only ever holds the `Finish` for the
+    /// joined return, whose class this returns.
     pub fn finish(&mut self, pred: ClassId, value: ClassId) -> ClassId {
+        let site = (self.find(pred), self.find(value));
+        let seen = self.return_sites().any(|other| other == site);
+        let ret = match self.ret {
+            None => site,
+            Some(ret) if seen => ret,
+            Some((ret_pred, ret_value)) => {
+                let superseded = (self.find(ret_pred), self.find(ret_value));
+                let uf = &self.uf;
+                self.finish.retain(|det, dep| {
+                    !matches!(finish_decode(det, dep).canonicalize(uf),
+                        Term::Finish { pred, value, .. } if (pred, value) == superseded)
+                });
+                let region = self.region(ret_pred, pred);
+                if self.find(ret_value) == site.1 {
+                    (region, value)
+                } else {
+                    (region, self.phi(region, ret_value, value))
+                }
+            }
+        };
+        if !seen {
+            self.returns.push((pred, value));
+        }
+        self.ret = Some(ret);
         let root = self.makeset();
-        self.insert(Term::Finish { pred, value, root });
+        let finish = self.insert(Term::Finish {
+            pred: ret.0,
+            value: ret.1,
+            root,
+        });
+        self.find(finish.root())
+    }
+
+    /// Returns the same class for every call with the same `site`. Lowered functions take their
+    /// sites from `OpaqueSites`.
+    pub fn opaque(&mut self, site: u64) -> ClassId {
+        if let Some(root) = self.opaque_sites.get(&site) {
+            return self.find(*root);
+        }
+        let root = self.makeset();
+        let id = self.next_opaque;
+        self.next_opaque += 1;
+        self.insert(Term::Opaque { id, root });
         let root = self.find(root);
+        self.opaque_sites.insert(site, root);
         root
     }
 
+    pub fn return_sites(&self) -> impl Iterator<Item = (ClassId, ClassId)> + '_ {
+        self.returns
+            .iter()
+            .map(|(pred, value)| (self.find(*pred), self.find(*value)))
+    }
+
+    pub fn return_value(&self) -> Option<ClassId> {
+        self.ret.map(|(_, value)| self.find(value))
+    }
+
+    pub fn param_class(&self, index: u32) -> Option<ClassId> {
+        self.param
+            .iter()
+            .find(|(det, _)| det[1] == index)
+            .map(|(det, dep)| self.find(param_decode(&det, &dep).root()))
+    }
+
     pub fn phi(&mut self, region: ClassId, lhs: ClassId, rhs: ClassId) -> ClassId {
         let root = self.makeset();
         self.insert(Term::Phi {
@@ -518,8 +1929,7 @@ impl Graph {
             rhs,
             root,
         });
-        let root = self.find(root);
-        root
+        self.find(root)
     }
 
     pub fn rebuild(&mut self) {
@@ -528,47 +1938,233 @@ impl Graph {
 
             corebuild(self.terms().collect(), &mut self.uf);
 
-            changed = rebuild_enode_table(
+            changed = rebuild_table(
                 &mut self.constant,
                 &mut self.uf,
                 constant_encode,
                 constant_decode,
             ) || changed;
             changed =
-                rebuild_enode_table(&mut self.param, &mut self.uf, param_encode, param_decode)
-                    || changed;
+                rebuild_table(&mut self.param, &mut self.uf, param_encode, param_decode) || changed;
             changed =
-                rebuild_enode_table(&mut self.start, &mut self.uf, start_encode, start_decode)
-                    || changed;
-            changed =
-                rebuild_enode_table(&mut self.region, &mut self.uf, region_encode, region_decode)
-                    || changed;
-            changed =
-                rebuild_enode_table(&mut self.branch, &mut self.uf, branch_encode, branch_decode)
-                    || changed;
-            changed = rebuild_enode_table(
+                rebuild_table(&mut self.start, &mut self.uf, start_encode, start_decode) || changed;
+            changed = rebuild_table(&mut self.region, &mut self.uf, region_encode, region_decode)
+                || changed;
+            changed = rebuild_table(&mut self.branch, &mut self.uf, branch_encode, branch_decode)
+                || changed;
+            changed = rebuild_table(
                 &mut self.control_proj,
                 &mut self.uf,
                 control_proj_encode,
                 control_proj_decode,
             ) || changed;
+            changed = rebuild_table(&mut self.finish, &mut self.uf, finish_encode, finish_decode)
+                || changed;
+            changed = rebuild_table(&mut self.phi, &mut self.uf, phi_encode, phi_decode) || changed;
+            changed = rebuild_table(&mut self.add, &mut self.uf, add_encode, add_decode) || changed;
+            changed = rebuild_table(
+                &mut self.subtract,
+                &mut self.uf,
+                subtract_encode,
+                subtract_decode,
+            ) || changed;
+            changed = rebuild_table(
+                &mut self.multiply,
+                &mut self.uf,
+                multiply_encode,
+                multiply_decode,
+            ) || changed;
+            changed = rebuild_table(&mut self.divide, &mut self.uf, divide_encode, divide_decode)
+                || changed;
+            changed = rebuild_table(&mut self.equals, &mut self.uf, equals_encode, equals_decode)
+                || changed;
+            changed = rebuild_table(
+                &mut self.not_equals,
+                &mut self.uf,
+                not_equals_encode,
+                not_equals_decode,
+            ) || changed;
             changed =
-                rebuild_enode_table(&mut self.finish, &mut self.uf, finish_encode, finish_decode)
-                    || changed;
-            changed =
-                rebuild_enode_table(&mut self.phi, &mut self.uf, phi_encode, phi_decode) || changed;
-            changed =
-                rebuild_enode_table(&mut self.add, &mut self.uf, add_encode, add_decode) || changed;
+                rebuild_table(&mut self.less, &mut self.uf, less_encode, less_decode) || changed;
+            changed = rebuild_table(
+                &mut self.less_equals,
+                &mut self.uf,
+                less_equals_encode,
+                less_equals_decode,
+            ) || changed;
+            changed = rebuild_table(&mut self.opaque, &mut self.uf, opaque_encode, opaque_decode)
+                || changed;
 
             if !changed {
                 break;
             }
         }
+        rebuild_table(
+            &mut self.interval,
+            &mut self.uf,
+            class_interval_encode,
+            class_interval_decode,
+        );
+        self.dirty.clear();
+    }
+
+    pub fn class_interval(&self, id: ClassId) -> Option<Interval> {
+        self.interval
+            .map(&[self.find(id).idx()])
+            .map(interval_decode)
+    }
+
+    pub fn set_class_interval(&mut self, id: ClassId, interval: Interval) -> Interval {
+        let det = [self.find(id).idx()];
+        let (dep, _) = hash_cons(
+            &mut self.interval,
+            &self.uf,
+            &det,
+            &interval_encode(&interval),
+        );
+        interval_decode(&dep)
+    }
+
+    pub fn merge_and_rebuild(&mut self, a: ClassId, b: ClassId) -> bool {
+        let merged = self.merge_mut(a, b).1;
+        let rebuilt = !self.rebuild_tracked().is_empty();
+        merged || rebuilt
+    }
+
+    pub fn rebuild_tracked(&mut self) -> Vec<(ClassId, ClassId)> {
+        let before = self.uf.clone();
+        self.rebuild();
+        before
+            .iter()
+            .filter(|(id, rep)| id == rep)
+            .map(|(id, _)| (id, self.find(id)))
+            .filter(|(id, rep)| id != rep)
+            .collect()
+    }
+
+    pub fn finalize(&mut self) {
+        self.constant.finalize();
+        self.param.finalize();
+        self.start.finalize();
+        self.region.finalize();
+        self.branch.finalize();
+        self.control_proj.finalize();
+        self.finish.finalize();
+        self.phi.finalize();
+        self.add.finalize();
+        self.subtract.finalize();
+        self.multiply.finalize();
+        self.divide.finalize();
+        self.equals.finalize();
+        self.not_equals.finalize();
+        self.less.finalize();
+        self.less_equals.finalize();
+        self.opaque.finalize();
+        self.interval.finalize();
+    }
+
+    /// Relabels classes canonically by color refinement, individualizing each class of the first
+    /// tied color in turn and keeping the smallest result. Labels end up injective, so equal
+    /// signatures mean isomorphic graphs. The search is exponential for highly symmetric graphs,
+    /// so it gives up with `None` after `STRUCTURAL_SEARCH_BUDGET` refinements.
+    fn structural_signature(&self) -> Option<Vec<Term>> {
+        fn relabel(labels: &BTreeMap<ClassId, u32>, term: &Term) -> Term {
+            term.map_classes(|id| ClassId::new(labels[&id]))
+        }
+
+        fn refine(terms: &[Term], mut labels: BTreeMap<ClassId, u32>) -> BTreeMap<ClassId, u32> {
+            let mut num_labels = labels.values().collect::<BTreeSet<_>>().len();
+            loop {
+                let mut signatures: BTreeMap<ClassId, (u32, Vec<(usize, Term)>)> = labels
+                    .iter()
+                    .map(|(id, label)| (*id, (*label, vec![])))
+                    .collect();
+                for term in terms {
+                    let relabeled = relabel(&labels, term);
+                    let occurrences = Some(term.root()).into_iter().chain(term.operands());
+                    for (position, id) in occurrences.enumerate() {
+                        signatures
+                            .get_mut(&id)
+                            .unwrap()
+                            .1
+                            .push((position, relabeled));
+                    }
+                }
+                for (_, signature) in signatures.values_mut() {
+                    signature.sort();
+                }
+                let distinct: Vec<&(u32, Vec<(usize, Term)>)> = signatures
+                    .values()
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect();
+                labels = signatures
+                    .iter()
+                    .map(|(id, signature)| {
+                        (*id, distinct.binary_search(&signature).unwrap() as u32)
+                    })
+                    .collect();
+                if distinct.len() == num_labels {
+                    return labels;
+                }
+                num_labels = distinct.len();
+            }
+        }
+
+        fn canonical(
+            terms: &[Term],
+            labels: BTreeMap<ClassId, u32>,
+            budget: &mut usize,
+        ) -> Option<Vec<Term>> {
+            *budget = budget.checked_sub(1)?;
+            let labels = refine(terms, labels);
+            let mut cells: BTreeMap<u32, Vec<ClassId>> = BTreeMap::new();
+            for (id, label) in &labels {
+                cells.entry(*label).or_default().push(*id);
+            }
+            let Some(tied) = cells.values().find(|cell| cell.len() > 1) else {
+                let mut signature: Vec<Term> =
+                    terms.iter().map(|term| relabel(&labels, term)).collect();
+                signature.sort();
+                return Some(signature);
+            };
+            let mut best: Option<Vec<Term>> = None;
+            for id in tied {
+                let mut labels = labels.clone();
+                labels.insert(*id, cells.len() as u32);
+                let signature = canonical(terms, labels, budget)?;
+                if best.as_ref().is_none_or(|best| signature < *best) {
+                    best = Some(signature);
+                }
+            }
+            best
+        }
+
+        let terms: Vec<Term> = self
+            .terms()
+            .map(|term| term.canonicalize(&self.uf))
+            .collect();
+        let labels = terms
+            .iter()
+            .flat_map(|term| Some(term.root()).into_iter().chain(term.operands()))
+            .map(|id| (id, 0))
+            .collect();
+        let mut budget = STRUCTURAL_SEARCH_BUDGET;
+        canonical(&terms, labels, &mut budget)
+    }
+
+    /// Graphs too symmetric to canonicalize within the search budget compare unequal, even to
+    /// their own clones.
+    pub fn structurally_eq(&self, other: &Graph) -> bool {
+        match (self.structural_signature(), other.structural_signature()) {
+            (Some(lhs), Some(rhs)) => lhs == rhs,
+            _ => false,
+        }
     }
 
     pub fn dump(&self, interner: &StringInterner) -> String {
         format!(
-            "{}{}{}{}{}{}{}{}{}",
+            "{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
             self.constant.dump(interner),
             self.param.dump(interner),
             self.start.dump(interner),
@@ -577,38 +2173,214 @@ impl Graph {
             self.control_proj.dump(interner),
             self.finish.dump(interner),
             self.phi.dump(interner),
-            self.add.dump(interner)
+            self.add.dump(interner),
+            self.subtract.dump(interner),
+            self.multiply.dump(interner),
+            self.divide.dump(interner),
+            self.equals.dump(interner),
+            self.not_equals.dump(interner),
+            self.less.dump(interner),
+            self.less_equals.dump(interner),
+            self.opaque.dump(interner)
+        )
+    }
+
+    fn dump_determinant(&self, term: &Term, interner: &StringInterner) -> String {
+        match term {
+            Term::Constant { .. } => self
+                .constant
+                .dump_determinant(&constant_encode(term).0, interner),
+            Term::Param { .. } => self.param.dump_determinant(&param_encode(term).0, interner),
+            Term::Start { .. } => self.start.dump_determinant(&start_encode(term).0, interner),
+            Term::Region { .. } => self
+                .region
+                .dump_determinant(&region_encode(term).0, interner),
+            Term::Branch { .. } => self
+                .branch
+                .dump_determinant(&branch_encode(term).0, interner),
+            Term::ControlProj { .. } => self
+                .control_proj
+                .dump_determinant(&control_proj_encode(term).0, interner),
+            Term::Finish { .. } => self
+                .finish
+                .dump_determinant(&finish_encode(term).0, interner),
+            Term::Phi { .. } => self.phi.dump_determinant(&phi_encode(term).0, interner),
+            Term::Add { .. } => self.add.dump_determinant(&add_encode(term).0, interner),
+            Term::Subtract { .. } => self
+                .subtract
+                .dump_determinant(&subtract_encode(term).0, interner),
+            Term::Multiply { .. } => self
+                .multiply
+                .dump_determinant(&multiply_encode(term).0, interner),
+            Term::Divide { .. } => self
+                .divide
+                .dump_determinant(&divide_encode(term).0, interner),
+            Term::Equals { .. } => self
+                .equals
+                .dump_determinant(&equals_encode(term).0, interner),
+            Term::NotEquals { .. } => self
+                .not_equals
+                .dump_determinant(&not_equals_encode(term).0, interner),
+            Term::Less { .. } => self.less.dump_determinant(&less_encode(term).0, interner),
+            Term::LessEquals { .. } => self
+                .less_equals
+                .dump_determinant(&less_equals_encode(term).0, interner),
+            Term::Opaque { .. } => self
+                .opaque
+                .dump_determinant(&opaque_encode(term).0, interner),
+        }
+    }
+
+    pub fn dump_by_class(&self, interner: &StringInterner) -> String {
+        let mut classes: BTreeMap<ClassId, Vec<Term>> = BTreeMap::new();
+        for term in self.terms() {
+            classes
+                .entry(self.find(term.root()))
+                .or_default()
+                .push(term);
+        }
+        let mut s = String::new();
+        for (class, terms) in classes {
+            writeln!(s, "[{}]", class.idx()).unwrap();
+            for term in terms {
+                writeln!(
+                    s,
+                    "  {} -> [{}]",
+                    self.dump_determinant(&term, interner),
+                    term.root().idx()
+                )
+                .unwrap();
+            }
+        }
+        s
+    }
+}
+
+pub struct TermDisplay<'a> {
+    term: Term,
+    graph: &'a Graph,
+}
+
+impl Term {
+    pub fn display<'a>(&self, graph: &'a Graph) -> TermDisplay<'a> {
+        TermDisplay { term: *self, graph }
+    }
+}
+
+impl fmt::Display for TermDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Term::*;
+        let class = |id: ClassId| format!("c{}", self.graph.find(id).idx());
+        let (op, args) = match self.term {
+            Constant { value, .. } => ("cons", vec![value.to_string()]),
+            Param { start, index, .. } => ("param", vec![class(start), index.to_string()]),
+            Start { .. } => ("start", vec![]),
+            Region { lhs, rhs, .. } => ("region", vec![class(lhs), class(rhs)]),
+            Branch { pred, cond, .. } => ("branch", vec![class(pred), class(cond)]),
+            ControlProj { pred, index, .. } => ("π", vec![class(pred), index.to_string()]),
+            Finish { pred, value, .. } => ("finish", vec![class(pred), class(value)]),
+            Phi {
+                region, lhs, rhs, ..
+            } => ("ϕ", vec![class(region), class(lhs), class(rhs)]),
+            Add { lhs, rhs, .. } => ("+", vec![class(lhs), class(rhs)]),
+            Subtract { lhs, rhs, .. } => ("-", vec![class(lhs), class(rhs)]),
+            Multiply { lhs, rhs, .. } => ("*", vec![class(lhs), class(rhs)]),
+            Divide { lhs, rhs, .. } => ("/", vec![class(lhs), class(rhs)]),
+            Equals { lhs, rhs, .. } => ("==", vec![class(lhs), class(rhs)]),
+            NotEquals { lhs, rhs, .. } => ("!=", vec![class(lhs), class(rhs)]),
+            Less { lhs, rhs, .. } => ("<", vec![class(lhs), class(rhs)]),
+            LessEquals { lhs, rhs, .. } => ("<=", vec![class(lhs), class(rhs)]),
+            Opaque { id, .. } => ("?", vec![id.to_string()]),
+        };
+        write!(
+            f,
+            "{}({}) -> {}",
+            op,
+            args.join(", "),
+            class(self.term.root())
         )
     }
 }
 
+/// Stable opaque site ids for one function's expressions: the function's index in the high half
+/// and the expression's preorder position in the low half.
+pub struct OpaqueSites {
+    func: u32,
+    exprs: HashMap<*const (), u32>,
+}
+
+impl OpaqueSites {
+    pub fn new(func_idx: u32, func: &FunctionAST<'_>) -> Self {
+        struct Numbering {
+            exprs: HashMap<*const (), u32>,
+        }
+
+        impl Visitor for Numbering {
+            fn visit_expr(&mut self, expr: &ExpressionAST<'_>) {
+                let next = self.exprs.len() as u32;
+                self.exprs.insert(ptr::from_ref(expr).cast(), next);
+                walk_expr(expr, self);
+            }
+        }
+
+        let mut numbering = Numbering {
+            exprs: HashMap::new(),
+        };
+        numbering.visit_func(func);
+        Self {
+            func: func_idx,
+            exprs: numbering.exprs,
+        }
+    }
+
+    pub fn expr(&self, expr: &ExpressionAST<'_>) -> u64 {
+        let idx = self
+            .exprs
+            .get(&ptr::from_ref(expr).cast())
+            .expect("expression isn't part of the function");
+        ((self.func as u64) << 32) | *idx as u64
+    }
+
+    pub fn implicit_return(&self) -> u64 {
+        ((self.func as u64) << 32) | u32::MAX as u64
+    }
+}
+
+/// For each loop head state, its region and each variable's static phi class and latest phi.
+pub type StaticPhis<'a> =
+    HashMap<SSADomain<'a>, (ClassId, BTreeMap<IdentifierId, (ClassId, ClassId)>)>;
+
 #[derive(Clone)]
 pub struct SSADomain<'a> {
     ssa_values: BTreeMap<IdentifierId, ClassId>,
     pred: ClassId,
     graph: &'a RefCell<Graph>,
-    static_phis:
-        &'a RefCell<HashMap<SSADomain<'a>, (ClassId, BTreeMap<IdentifierId, (ClassId, ClassId)>)>>,
+    sites: &'a OpaqueSites,
+    static_phis: &'a RefCell<StaticPhis<'a>>,
     finished: Option<ClassId>,
 }
 
 impl<'a> SSADomain<'a> {
     pub fn new(
         graph: &'a RefCell<Graph>,
-        static_phis: &'a RefCell<
-            HashMap<SSADomain<'a>, (ClassId, BTreeMap<IdentifierId, (ClassId, ClassId)>)>,
-        >,
+        sites: &'a OpaqueSites,
+        static_phis: &'a RefCell<StaticPhis<'a>>,
         start: ClassId,
-        params: Vec<(IdentifierId, ClassId)>,
+        params: impl IntoIterator<Item = (IdentifierId, ClassId)>,
     ) -> Self {
         Self {
             ssa_values: params.into_iter().collect(),
             pred: start,
             graph,
+            sites,
             static_phis,
             finished: None,
         }
     }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_some()
+    }
 }
 
 impl PartialEq for SSADomain<'_> {
@@ -642,10 +2414,60 @@ impl AbstractDomain for SSADomain<'_> {
                 let rhs = self.interp_expr(rhs);
                 self.graph.borrow_mut().add(lhs, rhs)
             }
-            _ => todo!(),
+            Subtract(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().subtract(lhs, rhs)
+            }
+            Multiply(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().multiply(lhs, rhs)
+            }
+            Divide(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().divide(lhs, rhs)
+            }
+            EqualsEquals(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().equals(lhs, rhs)
+            }
+            NotEquals(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().not_equals(lhs, rhs)
+            }
+            Less(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().less(lhs, rhs)
+            }
+            LessEquals(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().less_equals(lhs, rhs)
+            }
+            Greater(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().less(rhs, lhs)
+            }
+            GreaterEquals(lhs, rhs) => {
+                let lhs = self.interp_expr(lhs);
+                let rhs = self.interp_expr(rhs);
+                self.graph.borrow_mut().less_equals(rhs, lhs)
+            }
+            BlockExpr(block, value) => ai_block_expr(block, value, self),
+            _ => self.opaque_expr(expr),
         }
     }
 
+    fn opaque_expr(&self, expr: &ExpressionAST<'_>) -> ClassId {
+        self.graph.borrow_mut().opaque(self.sites.expr(expr))
+    }
+
     fn get(&self, iden: IdentifierId) -> ClassId {
         self.ssa_values[&iden]
     }
@@ -654,7 +2476,7 @@ impl AbstractDomain for SSADomain<'_> {
         self.ssa_values.insert(iden, val);
     }
 
-    fn branch(&self, cond: ClassId) -> (Self, Self) {
+    fn branch(&self, _cond: &ExpressionAST<'_>, cond: ClassId) -> (Self, Self) {
         let branch = self.graph.borrow_mut().branch(self.pred, cond);
         let true_proj = self.graph.borrow_mut().control_proj(branch, 1);
         let false_proj = self.graph.borrow_mut().control_proj(branch, 0);
@@ -666,13 +2488,17 @@ impl AbstractDomain for SSADomain<'_> {
     }
 
     fn finish_with(&mut self, val: ClassId) {
-        self.finished = Some(self.graph.borrow_mut().finish(self.pred, val));
+        self.graph.borrow_mut().finish(self.pred, val);
+        self.finished = Some(val);
     }
 
     fn join(&self, other: &Self) -> Self {
+        if other.finished.is_some() {
+            return self.clone();
+        } else if self.finished.is_some() {
+            return other.clone();
+        }
         assert_ne!(self.pred, other.pred);
-        assert!(self.finished.is_none());
-        assert!(other.finished.is_none());
         let region = self.graph.borrow_mut().region(self.pred, other.pred);
         let mut merged = BTreeMap::new();
         for (self_iden, self_ssa) in &self.ssa_values {
@@ -691,6 +2517,7 @@ impl AbstractDomain for SSADomain<'_> {
             ssa_values: merged,
             pred: region,
             graph: self.graph,
+            sites: self.sites,
             static_phis: self.static_phis,
             finished: None,
         }
@@ -714,6 +2541,8 @@ impl AbstractDomain for SSADomain<'_> {
             let static_phis = static_phis_borrow.get_mut(self).unwrap();
             let region = static_phis.0;
             let static_phis = &mut static_phis.1;
+            // The entry edge goes first and the back-edge second, which `Graph::back_edge` relies
+            // on.
             self.graph.borrow_mut().insert(Term::Region {
                 lhs: self.pred,
                 rhs: other.pred,
@@ -742,8 +2571,8 @@ impl AbstractDomain for SSADomain<'_> {
             }
 
             if !changed {
-                for (_, (static_phi, last_expr)) in static_phis {
-                    self.graph.borrow_mut().merge(*static_phi, *last_expr);
+                for (static_phi, last_expr) in static_phis.values() {
+                    self.graph.borrow_mut().merge_mut(*static_phi, *last_expr);
                 }
             }
 
@@ -752,6 +2581,7 @@ impl AbstractDomain for SSADomain<'_> {
                     ssa_values: merged,
                     pred: region,
                     graph: self.graph,
+                    sites: self.sites,
                     static_phis: self.static_phis,
                     finished: None,
                 },
@@ -768,8 +2598,10 @@ mod tests {
     use util::arena::Arena;
     use util::interner::StringInterner;
 
+    use crate::ai::abstract_interpret;
+    use crate::grammar::ProgramParser;
+
     #[test]
-    #[cfg_attr(miri, ignore)]
     fn hash_cons() {
         let mut buf: [u64; 100] = [0; 100];
         let arena = Arena::new_backed(&mut buf);
@@ -838,7 +2670,6 @@ mod tests {
     }
 
     #[test]
-    #[cfg_attr(miri, ignore)]
     fn rebuild() {
         let mut buf: [u64; 100] = [0; 100];
         let arena = Arena::new_backed(&mut buf);
@@ -914,4 +2745,1663 @@ mod tests {
         db.rebuild();
         assert_eq!(db.find(fab), db.find(fcd));
     }
+
+    #[test]
+    fn contains() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(1);
+        let b = db.constant(2);
+        let c = db.makeset();
+        let ab = db.add(a, b);
+        assert!(db.contains(Term::Add {
+            lhs: a,
+            rhs: b,
+            root: c,
+        }));
+        assert_eq!(
+            db.find_term(Term::Add {
+                lhs: a,
+                rhs: b,
+                root: c,
+            }),
+            Some(Term::Add {
+                lhs: a,
+                rhs: b,
+                root: ab,
+            })
+        );
+        assert!(!db.contains(Term::Add {
+            lhs: b,
+            rhs: a,
+            root: c,
+        }));
+        assert!(db.contains(Term::Constant { value: 2, root: c }));
+        assert!(!db.contains(Term::Constant { value: 3, root: c }));
+    }
+
+    #[test]
+    fn table_rows() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(1);
+        let b = db.constant(2);
+        let ab = db.add(a, b);
+        let ba = db.add(b, a);
+
+        let rows = db.table_rows(interner.intern("+")).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (vec![a.idx(), b.idx()], vec![ab.idx()]),
+                (vec![b.idx(), a.idx()], vec![ba.idx()]),
+            ]
+        );
+        assert_eq!(db.table_rows(interner.intern("cons")).unwrap().len(), 2);
+        assert_eq!(db.table_rows(interner.intern("%")), None);
+    }
+
+    #[test]
+    fn deterministic_corebuild() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.makeset();
+        let b = db.makeset();
+        let c = db.makeset();
+        let d = db.makeset();
+        let ab = db.add(a, b);
+        let cd = db.add(c, d);
+        db.add(ab, cd);
+        db.add(cd, ab);
+        db.insert(Term::Constant { value: 2, root: a });
+        db.insert(Term::Constant { value: 3, root: b });
+        db.insert(Term::Constant { value: 2, root: c });
+        db.insert(Term::Constant { value: 3, root: d });
+
+        let mut first = db.uf.clone();
+        let mut second = db.uf.clone();
+        let terms: Vec<Term> = db.terms().map(|term| term.canonicalize(&db.uf)).collect();
+        let first_merges = corebuild(terms.clone(), &mut first);
+        let second_merges = corebuild(terms, &mut second);
+        assert_eq!(first_merges, second_merges);
+        assert_eq!(first_merges, 2);
+        for idx in 0..db.uf.num_classes() {
+            let id = ClassId::new(idx);
+            assert_eq!(first.find(id), second.find(id));
+        }
+        assert_eq!(first.find(ab), first.find(cd));
+    }
+
+    #[test]
+    fn corebuild_distinct_cycles() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let x = db.opaque(0);
+        let y = db.opaque(1);
+        let zero = db.constant(0);
+        let x_zero = db.add(x, zero);
+        let y_zero = db.add(y, zero);
+        db.merge_and_rebuild(x_zero, x);
+        db.merge_and_rebuild(y_zero, y);
+        assert_ne!(db.find(x), db.find(y));
+
+        let terms: Vec<Term> = db.terms().collect();
+        let mut uf = db.uf.clone();
+        assert_eq!(corebuild(terms.clone(), &mut uf), 0);
+        assert_eq!(db::rebuild::corebuild_subset(terms, &mut uf, &[x, y]), 0);
+        assert_ne!(uf.find(x), uf.find(y));
+    }
+
+    #[test]
+    fn corebuild_subset() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.makeset();
+        let b = db.makeset();
+        let c = db.makeset();
+        let d = db.makeset();
+        let ab = db.add(a, b);
+        let cd = db.add(c, d);
+        db.add(ab, cd);
+        db.add(cd, ab);
+        let e = db.constant(7);
+        let f = db.constant(8);
+        let ef = db.add(e, f);
+        db.add(ef, e);
+        db.insert(Term::Constant { value: 2, root: a });
+        db.insert(Term::Constant { value: 3, root: b });
+        db.insert(Term::Constant { value: 2, root: c });
+        db.insert(Term::Constant { value: 3, root: d });
+
+        let mut full = db.uf.clone();
+        let mut subset = db.uf.clone();
+        let terms: Vec<Term> = db.terms().map(|term| term.canonicalize(&db.uf)).collect();
+        let full_merges = corebuild(terms.clone(), &mut full);
+        let subset_merges =
+            db::rebuild::corebuild_subset(terms.clone(), &mut subset, &[a, b, c, d]);
+        assert_eq!(full_merges, subset_merges);
+        for idx in 0..db.uf.num_classes() {
+            let id = ClassId::new(idx);
+            assert_eq!(full.find(id), subset.find(id));
+        }
+        assert_eq!(subset.find(ab), subset.find(cd));
+
+        let mut untouched = db.uf.clone();
+        assert_eq!(
+            db::rebuild::corebuild_subset(terms.clone(), &mut untouched, &[]),
+            0
+        );
+        assert_eq!(
+            db::rebuild::corebuild_subset(terms, &mut untouched, &[e, f]),
+            0
+        );
+        assert_eq!(untouched, db.uf);
+    }
+
+    #[test]
+    fn structural_eq() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(1);
+        let b = db.constant(2);
+        let ab = db.add(a, b);
+        db.add(ab, a);
+
+        let mut other = Graph::new(&mut interner);
+        other.makeset();
+        let other_b = other.constant(2);
+        let other_a = other.constant(1);
+        let other_ab = other.add(other_a, other_b);
+        other.add(other_ab, other_a);
+        assert!(db.structurally_eq(&other));
+        other.add(other_a, other_a);
+        assert!(!db.structurally_eq(&other));
+
+        let mut clone = db.clone();
+        assert!(db.structurally_eq(&clone));
+        assert!(clone.structurally_eq(&db));
+        assert_eq!(clone.dump(&interner), db.dump(&interner));
+
+        clone.merge_mut(a, b);
+        assert!(!db.structurally_eq(&clone));
+        assert_eq!(clone.find(a), clone.find(b));
+        assert_ne!(db.find(a), db.find(b));
+
+        let mut distinct = Graph::new(&mut interner);
+        let x = distinct.makeset();
+        let y = distinct.makeset();
+        distinct.add(x, y);
+        let mut same = Graph::new(&mut interner);
+        let x = same.makeset();
+        same.makeset();
+        same.add(x, x);
+        assert!(!distinct.structurally_eq(&same));
+        assert!(!same.structurally_eq(&distinct));
+
+        let mut swapped = Graph::new(&mut interner);
+        let y = swapped.makeset();
+        let x = swapped.makeset();
+        swapped.add(x, y);
+        assert!(distinct.structurally_eq(&swapped));
+
+        // Eight interchangeable self-loops take 8! individualizations to canonicalize.
+        let mut symmetric = Graph::new(&mut interner);
+        for _ in 0..8 {
+            let x = symmetric.makeset();
+            symmetric.insert(Term::Add {
+                lhs: x,
+                rhs: x,
+                root: x,
+            });
+        }
+        assert!(!symmetric.structurally_eq(&symmetric.clone()));
+    }
+
+    #[test]
+    fn canonical_terms() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.makeset();
+        let b = db.makeset();
+        let ab = db.add(a, b);
+        let ba = db.add(b, a);
+        db.merge_mut(b, a);
+        let rep = db.find(a);
+        assert_eq!(rep, db.find(b));
+
+        let raw: Vec<Term> = db.terms().collect();
+        assert!(raw.contains(&Term::Add {
+            lhs: b,
+            rhs: a,
+            root: ba,
+        }));
+        let canonical: Vec<Term> = db.canonical_terms().collect();
+        assert_eq!(canonical.len(), raw.len());
+        assert!(canonical.contains(&Term::Add {
+            lhs: rep,
+            rhs: rep,
+            root: ab,
+        }));
+        assert!(canonical.contains(&Term::Add {
+            lhs: rep,
+            rhs: rep,
+            root: ba,
+        }));
+        assert!(
+            canonical
+                .iter()
+                .all(|term| term.operands().iter().all(|id| db.find(*id) == *id))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn opaque() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn opaque(x) { while x % 10 { x = x * 2; } return x + 1; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner)
+            .unwrap()
+            .into_graphs();
+        assert_eq!(graphs.len(), 1);
+        let mut graph = graphs.pop().unwrap();
+        graph.rebuild();
+
+        let opaques = graph.table_rows(interner.intern("?")).unwrap();
+        assert_eq!(opaques.len(), 1);
+        assert!(
+            graph
+                .canonical_terms()
+                .any(|term| matches!(term, Term::Multiply { .. }))
+        );
+        let ret = graph.return_value().unwrap();
+        let one = graph.constant(1);
+        assert!(graph.canonical_terms().any(|term| match term {
+            Term::Add { lhs, rhs, root } => root == ret && rhs == one && lhs != one,
+            _ => false,
+        }));
+        assert!(graph.canonical_terms().any(|term| match term {
+            Term::Branch { cond, .. } => opaques.iter().any(|(_, dep)| dep[0] == cond.idx()),
+            _ => false,
+        }));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn opaque_sites() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+
+        let program = "fn first(x) { return x % 3; } fn second(y) { z = y % 2; }";
+        let sites = |interner: &mut StringInterner| {
+            let mut buf: [u8; 10000] = [0; 10000];
+            let arena = Arena::new_backed(&mut buf);
+            let program = ProgramParser::new()
+                .parse(&arena, interner, program)
+                .unwrap();
+            abstract_interpret(&program, interner)
+                .unwrap()
+                .iter()
+                .map(|graph| graph.opaque_sites.keys().copied().collect::<BTreeSet<_>>())
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            BTreeSet::from([0]),
+            BTreeSet::from([1 << 32, (1 << 32) | u32::MAX as u64]),
+        ];
+        assert_eq!(sites(&mut interner), expected);
+        assert_eq!(sites(&mut interner), expected);
+    }
+
+    #[test]
+    fn dirty_merge() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.makeset();
+        let b = db.makeset();
+        let c = db.makeset();
+        db.add(a, c);
+        db.add(b, c);
+        assert_eq!(db.dirty_classes().count(), 0);
+
+        let (merged, changed) = db.merge_mut(a, b);
+        assert!(changed);
+        assert_eq!(merged, db.find(b));
+        assert_eq!(db.dirty_classes().collect::<Vec<_>>(), vec![a, b]);
+        assert_eq!(db.merge_mut(b, a), (merged, false));
+        assert_eq!(db.dirty_classes().count(), 2);
+
+        db.rebuild();
+        assert_eq!(db.dirty_classes().count(), 0);
+        assert_eq!(db.add.num_allocated_rows, 1);
+
+        let d = db.makeset();
+        #[allow(deprecated)]
+        let merged = db.merge(c, d);
+        assert_eq!(merged, db.find(c));
+        assert_eq!(db.find(c), db.find(d));
+        assert_eq!(db.dirty_classes().count(), 0);
+    }
+
+    #[test]
+    fn structural_key() {
+        let a = ClassId::new(0);
+        let b = ClassId::new(1);
+        let add = |root| Term::Add {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let region = Term::Region {
+            lhs: a,
+            rhs: b,
+            root: ClassId::new(2),
+        };
+        assert_eq!(
+            add(ClassId::new(2)).structural_key(),
+            add(ClassId::new(3)).structural_key()
+        );
+        assert_ne!(
+            add(ClassId::new(2)).structural_key(),
+            region.structural_key()
+        );
+        assert_ne!(
+            add(ClassId::new(2)).structural_key(),
+            Term::Add {
+                lhs: b,
+                rhs: a,
+                root: ClassId::new(2)
+            }
+            .structural_key()
+        );
+        assert_eq!(
+            Term::Constant { value: -1, root: a }.structural_key().1,
+            vec![u32::MAX]
+        );
+
+        let mut keys = HashMap::new();
+        keys.insert(add(ClassId::new(2)).structural_key(), ClassId::new(2));
+        assert_eq!(
+            keys.get(&add(ClassId::new(3)).structural_key()),
+            Some(&ClassId::new(2))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn lower_comparisons() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program =
+            "fn cmp(x) { if x < 3 { x = 1; } if 3 > x { x = 2; } y = x <= 3; return x == y; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graph = abstract_interpret(&program, &mut interner)
+            .unwrap()
+            .into_graphs()
+            .pop()
+            .unwrap();
+        graph.rebuild();
+
+        assert_eq!(graph.table_rows(interner.intern("?")).unwrap().len(), 0);
+        assert_eq!(graph.table_rows(interner.intern("<=")).unwrap().len(), 1);
+        assert_eq!(graph.table_rows(interner.intern("==")).unwrap().len(), 1);
+        assert_eq!(graph.table_rows(interner.intern("!=")).unwrap().len(), 0);
+        let less = graph.table_rows(interner.intern("<")).unwrap();
+        assert_eq!(less.len(), 2);
+
+        let three = graph.constant(3);
+        let conds: Vec<ClassId> = graph
+            .canonical_terms()
+            .filter_map(|term| match term {
+                Term::Branch { cond, .. } => Some(cond),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(conds.len(), 2);
+        for cond in conds {
+            let Some(Term::Less { rhs, .. }) = graph
+                .canonical_terms()
+                .find(|term| term.root() == cond && matches!(term, Term::Less { .. }))
+            else {
+                panic!()
+            };
+            assert_eq!(rhs, three);
+        }
+
+        let x = graph.makeset();
+        let first = graph.less(x, three);
+        let second = graph.less(x, three);
+        assert_eq!(first, second);
+        assert_ne!(first, graph.less(three, x));
+        assert_eq!(graph.table_rows(interner.intern("<")).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn rebuild_tracked() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(1);
+        let b = db.constant(2);
+        let c = db.makeset();
+        let d = db.makeset();
+        let ab = db.add(a, b);
+        let cd = db.add(c, d);
+        db.insert(Term::Constant { value: 1, root: c });
+        db.insert(Term::Constant { value: 2, root: d });
+        assert_ne!(db.find(ab), db.find(cd));
+
+        let merges = db.rebuild_tracked();
+        assert_eq!(db.find(ab), db.find(cd));
+        let rep = db.find(ab);
+        assert!(merges.contains(&(ab, rep)) || merges.contains(&(cd, rep)));
+        assert!(
+            merges
+                .iter()
+                .all(|(from, to)| from != to && db.find(*to) == *to)
+        );
+        assert!(db.rebuild_tracked().is_empty());
+    }
+
+    #[test]
+    fn num_classes() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        assert_eq!(db.num_classes(), 0);
+        let a = db.makeset();
+        let b = db.makeset();
+        assert_eq!(db.num_classes(), 2);
+        db.constant(1);
+        assert_eq!(db.num_classes(), 3);
+        db.merge_mut(a, b);
+        assert_eq!(db.num_classes(), 3);
+        assert_eq!(db.union_find().num_classes(), 3);
+        assert_eq!(
+            db.union_find().iter().collect::<Vec<_>>(),
+            vec![(a, a), (b, a), (ClassId::new(2), ClassId::new(2))]
+        );
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let [a, b, c, root] = [3, 7, 11, 42].map(ClassId::new);
+        let check = |term: Term, round_trip: Term| assert_eq!(term, round_trip);
+        for value in [0, 1, -1, i32::MIN, i32::MAX] {
+            let term = Term::Constant { value, root };
+            let (det, dep) = constant_encode(&term);
+            check(term, constant_decode(&det, &dep));
+        }
+        for index in [0, 1, u32::MAX] {
+            let term = Term::Param {
+                start: a,
+                index,
+                root,
+            };
+            let (det, dep) = param_encode(&term);
+            check(term, param_decode(&det, &dep));
+            let term = Term::ControlProj {
+                pred: a,
+                index,
+                root,
+            };
+            let (det, dep) = control_proj_encode(&term);
+            check(term, control_proj_decode(&det, &dep));
+            let term = Term::Opaque { id: index, root };
+            let (det, dep) = opaque_encode(&term);
+            check(term, opaque_decode(&det, &dep));
+        }
+        let term = Term::Start { root };
+        let (det, dep) = start_encode(&term);
+        check(term, start_decode(&det, &dep));
+        let term = Term::Region {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = region_encode(&term);
+        check(term, region_decode(&det, &dep));
+        let term = Term::Branch {
+            pred: a,
+            cond: b,
+            root,
+        };
+        let (det, dep) = branch_encode(&term);
+        check(term, branch_decode(&det, &dep));
+        let term = Term::Finish {
+            pred: a,
+            value: b,
+            root,
+        };
+        let (det, dep) = finish_encode(&term);
+        check(term, finish_decode(&det, &dep));
+        let term = Term::Phi {
+            region: a,
+            lhs: b,
+            rhs: c,
+            root,
+        };
+        let (det, dep) = phi_encode(&term);
+        check(term, phi_decode(&det, &dep));
+        let term = Term::Add {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = add_encode(&term);
+        check(term, add_decode(&det, &dep));
+        let term = Term::Subtract {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = subtract_encode(&term);
+        check(term, subtract_decode(&det, &dep));
+        let term = Term::Multiply {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = multiply_encode(&term);
+        check(term, multiply_decode(&det, &dep));
+        let term = Term::Divide {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = divide_encode(&term);
+        check(term, divide_decode(&det, &dep));
+        let term = Term::Equals {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = equals_encode(&term);
+        check(term, equals_decode(&det, &dep));
+        let term = Term::NotEquals {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = not_equals_encode(&term);
+        check(term, not_equals_decode(&det, &dep));
+        let term = Term::Less {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = less_encode(&term);
+        check(term, less_decode(&det, &dep));
+        let term = Term::LessEquals {
+            lhs: a,
+            rhs: b,
+            root,
+        };
+        let (det, dep) = less_equals_encode(&term);
+        check(term, less_equals_decode(&det, &dep));
+    }
+
+    #[test]
+    fn merge_policy() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let a = db.makeset();
+        let b = db.makeset();
+        let c = db.makeset();
+        let d = db.makeset();
+        db.insert(Term::Add {
+            lhs: a,
+            rhs: b,
+            root: c,
+        });
+        db.insert(Term::Add {
+            lhs: a,
+            rhs: b,
+            root: d,
+        });
+        assert_eq!(db.find(c), db.find(d));
+
+        let meet = |new: &[u32; 2], old: &[u32; 2]| {
+            [
+                (new[0] as i32).max(old[0] as i32) as u32,
+                (new[1] as i32).min(old[1] as i32) as u32,
+            ]
+        };
+        let mut interval: Table<1, 2> =
+            Table::new_with_merge_policy(interner.intern("interval"), MergePolicy::Meet(meet));
+        let uf = UnionFind::new();
+        let first = [-5i32 as u32, 10];
+        let second = [0, 20];
+        assert_eq!(
+            super::hash_cons(&mut interval, &uf, &[a.idx()], &first).0,
+            first
+        );
+        assert_eq!(
+            super::hash_cons(&mut interval, &uf, &[a.idx()], &second).0,
+            [0, 10]
+        );
+        assert_eq!(interval.map(&[a.idx()]), Some(&[0, 10]));
+        assert_eq!(interval.iter().count(), 1);
+    }
+
+    #[test]
+    fn dump_signed_constant() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let minus_one = db.constant(-1);
+        db.constant(i32::MIN);
+        assert_eq!(db.constant_value(minus_one), Some(-1));
+        assert_eq!(
+            db.dump(&interner),
+            "cons([-1]) -> [0]\ncons([-2147483648]) -> [1]\n"
+        );
+        let _ = db.renumber_dense();
+        assert!(db.dump(&interner).starts_with("cons([-1]) -> [0]\n"));
+    }
+
+    #[test]
+    fn merge_and_rebuild() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let x = db.makeset();
+        let y = db.makeset();
+        db.insert(Term::Constant { value: 1, root: x });
+        db.insert(Term::Constant { value: 2, root: y });
+        let xx = db.add(x, x);
+        let yy = db.add(y, y);
+        let cmp_x = db.less(xx, x);
+        let cmp_y = db.less(yy, y);
+        assert_ne!(db.find(cmp_x), db.find(cmp_y));
+        assert!(db.merge_and_rebuild(x, y));
+        assert_eq!(db.find(xx), db.find(yy));
+        assert_eq!(db.find(cmp_x), db.find(cmp_y));
+        assert_eq!(db.dirty_classes().count(), 0);
+        assert!(!db.merge_and_rebuild(x, y));
+        assert!(!db.merge_and_rebuild(xx, yy));
+    }
+
+    #[test]
+    fn param_class() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let x = db.makeset();
+        db.insert(Term::Param {
+            start,
+            index: 0,
+            root: x,
+        });
+        let y = db.makeset();
+        db.insert(Term::Param {
+            start,
+            index: 1,
+            root: y,
+        });
+        assert_eq!(db.param_class(0), Some(x));
+        assert_eq!(db.param_class(1), Some(y));
+        assert_eq!(db.param_class(2), None);
+        db.merge_mut(x, y);
+        assert_eq!(db.param_class(1), Some(x));
+    }
+
+    #[test]
+    fn merge_all() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let x = db.opaque(0);
+        let y = db.opaque(1);
+        let z = db.opaque(2);
+        let xy = db.add(x, y);
+        let xz = db.add(x, z);
+        assert_eq!(db.merge_all(&[]), None);
+        assert_eq!(db.merge_all(&[x]), Some(x));
+        assert_eq!(db.dirty_classes().count(), 0);
+        let rep = db.merge_all(&[z, y, y]).unwrap();
+        assert_eq!(rep, db.find(y));
+        assert_eq!(rep, db.find(z));
+        assert_ne!(rep, db.find(x));
+        assert_eq!(db.dirty_classes().collect::<Vec<_>>(), vec![y, z]);
+        db.rebuild();
+        assert_eq!(db.find(xy), db.find(xz));
+    }
+
+    #[test]
+    fn replace_all_uses() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let x = db.constant(1);
+        let y = db.constant(2);
+        let sum = db.add(x, x);
+        let cmp = db.less(sum, x);
+        let rep = db.replace_all_uses(x, y);
+        assert_eq!(rep, db.find(x));
+        assert_eq!(rep, db.find(y));
+
+        let terms: Vec<_> = db.terms().collect();
+        assert!(terms.contains(&Term::Add {
+            lhs: y,
+            rhs: y,
+            root: sum
+        }));
+        assert!(terms.contains(&Term::Less {
+            lhs: sum,
+            rhs: y,
+            root: cmp
+        }));
+        assert!(terms.contains(&Term::Constant { value: 1, root: y }));
+        assert!(
+            terms
+                .iter()
+                .all(|term| term.operands().iter().all(|id| *id != x))
+        );
+        assert!(terms.iter().all(|term| term.root() != x));
+    }
+
+    #[test]
+    fn display_term() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let a = db.constant(-3);
+        let b = db.makeset();
+        db.insert(Term::Param {
+            start,
+            index: 1,
+            root: b,
+        });
+        let sum = db.add(a, b);
+        let terms: Vec<String> = db
+            .terms()
+            .map(|term| term.display(&db).to_string())
+            .collect();
+        assert!(terms.contains(&format!("start() -> c{}", start.idx())));
+        assert!(terms.contains(&format!("cons(-3) -> c{}", a.idx())));
+        assert!(terms.contains(&format!("param(c{}, 1) -> c{}", start.idx(), b.idx())));
+        assert!(terms.contains(&format!("+(c{}, c{}) -> c{}", a.idx(), b.idx(), sum.idx())));
+
+        db.merge_mut(a, b);
+        let term = Term::Add {
+            lhs: a,
+            rhs: b,
+            root: sum,
+        };
+        let rep = db.find(a).idx();
+        assert_eq!(
+            term.display(&db).to_string(),
+            format!("+(c{}, c{}) -> c{}", rep, rep, sum.idx())
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn back_edge() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn f(x, y) { if y { x = 1; } while x { x = x + -1; } while y { y = y + -1; } return x; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        let graph = &mut graphs[0];
+        graph.rebuild();
+        let regions: Vec<_> = graph
+            .canonical_terms()
+            .filter_map(|term| match term {
+                Term::Region { lhs, rhs, root } => Some((lhs, rhs, root)),
+                _ => None,
+            })
+            .collect();
+        let roots: BTreeSet<_> = regions.iter().map(|(_, _, root)| *root).collect();
+        assert_eq!(roots.len(), 3);
+        // Neither body has control flow of its own, so each back-edge is the true projection of
+        // the branch on its loop head.
+        let mut branch_preds = BTreeMap::new();
+        let mut proj_preds = BTreeMap::new();
+        for term in graph.canonical_terms() {
+            match term {
+                Term::Branch { pred, root, .. } => branch_preds.insert(root, pred),
+                Term::ControlProj { pred, root, .. } => proj_preds.insert(root, pred),
+                _ => None,
+            };
+        }
+        let projects_branch_on = |proj: ClassId, head: ClassId| {
+            proj_preds
+                .get(&proj)
+                .and_then(|branch| branch_preds.get(branch))
+                == Some(&head)
+        };
+        let start = graph.find(ClassId::new(0));
+        let mut loops = 0;
+        for root in roots {
+            match graph.back_edge(root) {
+                Some(back_edge) => {
+                    assert!(projects_branch_on(back_edge, root));
+                    loops += 1;
+                }
+                None => assert!(regions.iter().any(|(lhs, rhs, region)| *region == root
+                    && projects_branch_on(*lhs, start)
+                    && projects_branch_on(*rhs, start))),
+            }
+        }
+        assert_eq!(loops, 2);
+
+        let mut graph = Graph::new(&mut interner);
+        let start = graph.makeset();
+        graph.insert(Term::Start { root: start });
+        assert_eq!(graph.back_edge(start), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn topo_terms() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn basic(x) { while x { x = x + -1; } return x; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        graphs[0].rebuild();
+        let order = graphs[0].topo_terms().unwrap();
+        assert_eq!(order.len(), graphs[0].num_enodes());
+        let position = |class: ClassId| order.iter().position(|term| term.root() == class).unwrap();
+        for (idx, term) in order.iter().enumerate() {
+            if let Term::Add { lhs, rhs, .. } = term {
+                assert!(position(*lhs) < idx);
+                assert!(position(*rhs) < idx);
+                assert!(matches!(order[position(*rhs)], Term::Constant { .. }));
+            }
+        }
+
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(1);
+        let b = db.makeset();
+        let sum = db.add(a, b);
+        db.merge_mut(sum, b);
+        assert_eq!(db.topo_terms(), Err(db.find(b)));
+    }
+
+    #[test]
+    fn dump_by_class() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let a = db.constant(1);
+        let b = db.constant(2);
+        let ab = db.add(a, b);
+        let ba = db.add(b, a);
+        db.merge_mut(ab, ba);
+        db.merge_mut(a, b);
+        db.rebuild();
+        assert_eq!(
+            db.dump_by_class(&interner),
+            "[0]\n  cons([1]) -> [0]\n  cons([2]) -> [0]\n[2]\n  +([0, 0]) -> [2]\n"
+        );
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(1);
+        let b = db.constant(2);
+        let ab = db.add(a, b);
+        let ba = db.add(b, a);
+        db.merge_mut(ab, ba);
+        assert_eq!(
+            db.dump_by_class(&interner),
+            "[0]\n  cons([1]) -> [0]\n[1]\n  cons([2]) -> [1]\n[2]\n  +([0, 1]) -> [2]\n  +([1, 0]) -> [3]\n"
+        );
+
+        let mut db = Graph::new(&mut interner);
+        db.constant(-1);
+        assert_eq!(db.dump_by_class(&interner), "[0]\n  cons([-1]) -> [0]\n");
+    }
+
+    #[test]
+    fn try_insert() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let a = db.constant(1);
+        let root = db.makeset();
+        let add = Term::Add {
+            lhs: a,
+            rhs: a,
+            root,
+        };
+        assert_eq!(db.try_insert(add), Ok(add));
+        assert!(db.contains(add));
+
+        let root = db.makeset();
+        let out_of_range = ClassId::new(db.num_classes());
+        assert_eq!(
+            db.try_insert(Term::Add {
+                lhs: a,
+                rhs: out_of_range,
+                root
+            }),
+            Err(InsertError::OutOfRange(out_of_range))
+        );
+        assert_eq!(
+            db.try_insert(Term::Phi {
+                region: ClassId::INVALID,
+                lhs: a,
+                rhs: a,
+                root
+            }),
+            Err(InsertError::OutOfRange(ClassId::INVALID))
+        );
+        assert_eq!(
+            db.try_insert(Term::Constant {
+                value: 2,
+                root: ClassId::new(100)
+            }),
+            Err(InsertError::OutOfRange(ClassId::new(100)))
+        );
+        assert_eq!(db.num_enodes(), 2);
+    }
+
+    #[test]
+    fn absorb() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let five = db.constant(5);
+        let x = db.opaque(0);
+        db.add(five, x);
+
+        let mut other = Graph::new(&mut interner);
+        let other_five = other.constant(5);
+        let y = other.opaque(1);
+        let other_sum = other.add(other_five, y);
+        let z = other.makeset();
+        other.merge_mut(z, other_sum);
+
+        let remap = db.absorb(other);
+        assert_eq!(remap(other_five), ClassId::new(3));
+        assert_eq!(db.find(remap(z)), db.find(remap(other_sum)));
+        assert_eq!(db.find(remap(other_five)), db.find(five));
+        assert_ne!(db.find(remap(y)), db.find(x));
+        assert_eq!(db.opaque((1 << 32) | 1), db.find(remap(y)));
+        assert_ne!(db.opaque(1), db.find(remap(y)));
+        db.rebuild();
+        assert_eq!(db.find(remap(other_five)), db.find(five));
+        let sum = db.add(five, x);
+        assert_ne!(db.find(remap(other_sum)), db.find(sum));
+    }
+
+    #[test]
+    fn absorb_same_site() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let x = db.opaque(0);
+        let mut other = Graph::new(&mut interner);
+        let y = other.opaque(0);
+
+        let remap = db.absorb(other);
+        db.rebuild();
+        assert_ne!(db.find(remap(y)), db.find(x));
+        assert_eq!(db.opaque(0), db.find(x));
+        assert_eq!(db.opaque(1 << 32), db.find(remap(y)));
+    }
+
+    #[test]
+    fn iter_edges() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(2);
+        let b = db.constant(3);
+        let c = db.makeset();
+        let d = db.makeset();
+        db.insert(Term::Constant { value: 2, root: c });
+        db.insert(Term::Constant { value: 3, root: d });
+        let fab = db.add(a, b);
+        let fcd = db.add(c, d);
+        assert_eq!(db.iter_edges().count(), 4);
+        db.rebuild();
+        assert_eq!(db.find(fab), db.find(fcd));
+
+        let edges: Vec<_> = db.iter_edges().collect();
+        assert_eq!(edges.len(), 2);
+        assert!(
+            edges
+                .iter()
+                .all(|(term, _, _)| matches!(term, Term::Add { .. }))
+        );
+        let (term, _, _) = edges[0];
+        assert_eq!(edges, vec![(term, 0, db.find(a)), (term, 1, db.find(b))]);
+        assert_eq!(
+            edges
+                .iter()
+                .map(|(_, _, target)| *target)
+                .collect::<Vec<_>>(),
+            term.operands()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn check_phi_regions() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn basic(x) { while x { x = x + -1; } return x; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        graphs[0].rebuild();
+        assert_eq!(graphs[0].check_phi_regions(), Ok(()));
+
+        let mut db = Graph::new(&mut interner);
+        let a = db.constant(1);
+        let b = db.constant(2);
+        let phi = db.phi(a, a, b);
+        assert_eq!(
+            db.check_phi_regions(),
+            Err(format!(
+                "phi {} has region operand {} which is not a region",
+                phi.idx(),
+                a.idx()
+            ))
+        );
+
+        let mut db = Graph::new(&mut interner);
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let region = db.region(start, start);
+        let a = db.constant(1);
+        let phi = db.phi(region, a, start);
+        assert_eq!(
+            db.check_phi_regions(),
+            Err(format!(
+                "phi {} has input {} which is control, not a value",
+                phi.idx(),
+                start.idx()
+            ))
+        );
+    }
+
+    #[test]
+    fn renumber_dense() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let mut live = vec![];
+        for value in 0..8 {
+            db.makeset();
+            live.push(db.constant(value));
+        }
+        let sum = db.add(live[6], live[7]);
+        let other = db.add(live[7], live[6]);
+        db.merge_mut(sum, other);
+        db.rebuild();
+        let dead = db.makeset_many(4);
+        assert_eq!(db.num_classes(), 8 + 8 + 2 + 4);
+
+        let before: Vec<Term> = db.canonical_terms().collect();
+        let remap = db.renumber_dense();
+        assert_eq!(db.num_classes(), 8 + 1);
+        assert_eq!(db.num_enodes(), before.len());
+        for term in before {
+            assert!(db.contains(term.map_classes(&remap)));
+        }
+        for (value, id) in live.iter().enumerate() {
+            assert_eq!(db.constant_value(remap(*id)), Some(value as i32));
+        }
+        assert_eq!(remap(sum), remap(other));
+        assert_eq!(remap(ClassId::new(dead.start)), ClassId::INVALID);
+        assert!((0..db.num_classes()).all(|idx| db.find(ClassId::new(idx)) == ClassId::new(idx)));
+    }
+
+    #[test]
+    fn equivalent_terms() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let a = db.opaque(0);
+        let b = db.opaque(1);
+        let sum = db.add(a, b);
+        let query = Term::Add {
+            lhs: a,
+            rhs: b,
+            root: sum,
+        };
+        assert_eq!(db.equivalent_terms(query), vec![query]);
+
+        assert!(db.saturate(&crate::rules::arithmetic_rules(), 10));
+        let root = db.find(sum);
+        let mut terms = db.equivalent_terms(query);
+        terms.sort();
+        assert_eq!(
+            terms,
+            vec![
+                Term::Add {
+                    lhs: a,
+                    rhs: b,
+                    root
+                },
+                Term::Add {
+                    lhs: b,
+                    rhs: a,
+                    root
+                },
+            ]
+        );
+        assert!(
+            db.equivalent_terms(Term::Add {
+                lhs: a,
+                rhs: a,
+                root
+            })
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn control_and_value_terms() {
+        let [a, b, c, root] = [1, 2, 3, 4].map(ClassId::new);
+        let control = [
+            Term::Start { root },
+            Term::Region {
+                lhs: a,
+                rhs: b,
+                root,
+            },
+            Term::Branch {
+                pred: a,
+                cond: b,
+                root,
+            },
+            Term::ControlProj {
+                pred: a,
+                index: 0,
+                root,
+            },
+            Term::Finish {
+                pred: a,
+                value: b,
+                root,
+            },
+        ];
+        let value = [
+            Term::Constant { value: 0, root },
+            Term::Param {
+                start: a,
+                index: 0,
+                root,
+            },
+            Term::Phi {
+                region: a,
+                lhs: b,
+                rhs: c,
+                root,
+            },
+            Term::Add {
+                lhs: a,
+                rhs: b,
+                root,
+            },
+            Term::Equals {
+                lhs: a,
+                rhs: b,
+                root,
+            },
+            Term::NotEquals {
+                lhs: a,
+                rhs: b,
+                root,
+            },
+            Term::Less {
+                lhs: a,
+                rhs: b,
+                root,
+            },
+            Term::LessEquals {
+                lhs: a,
+                rhs: b,
+                root,
+            },
+            Term::Opaque { id: 0, root },
+        ];
+        for term in control {
+            assert!(term.is_control() && !term.is_value(), "{:?}", term);
+        }
+        for term in value {
+            assert!(term.is_value() && !term.is_control(), "{:?}", term);
+        }
+    }
+
+    #[test]
+    fn insert_get_id() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let one = db.constant(1);
+        let two = db.constant(2);
+        let root = db.makeset();
+        let (sum, row) = db.insert_get_id(Term::Add {
+            lhs: one,
+            rhs: two,
+            root,
+        });
+        assert_eq!(db.add.get_row(row), add_encode(&sum));
+
+        let other = db.makeset();
+        let (again, again_row) = db.insert_get_id(Term::Add {
+            lhs: one,
+            rhs: two,
+            root: other,
+        });
+        assert_eq!(again_row, row);
+        assert_eq!(again, sum);
+        assert_eq!(db.find(other), db.find(root));
+
+        let (cons, row) = db.insert_get_id(Term::Constant {
+            value: -3,
+            root: other,
+        });
+        assert_eq!(db.constant.get_row(row), constant_encode(&cons));
+    }
+
+    #[test]
+    fn simplify_control() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let one = db.constant(1);
+        let branch = db.branch(start, one);
+        let taken = db.control_proj(branch, 1);
+        let not_taken = db.control_proj(branch, 0);
+        let ten = db.constant(10);
+        let twenty = db.constant(20);
+        let region = db.region(taken, not_taken);
+        let phi = db.phi(region, ten, twenty);
+        db.finish(region, phi);
+        assert!(db.simplify_control());
+
+        assert_eq!(db.find(taken), db.find(start));
+        assert_eq!(db.find(region), db.find(start));
+        assert_eq!(db.find(phi), db.find(ten));
+        assert!(!db.canonical_terms().any(|term| matches!(
+            term,
+            Term::Branch { .. } | Term::ControlProj { .. } | Term::Region { .. } | Term::Phi { .. }
+        )));
+        assert!(!db.live_classes().contains(not_taken.idx() as usize));
+        assert_eq!(db.dead_code_classes(), vec![one, db.find(twenty)]);
+        assert!(!db.simplify_control());
+    }
+
+    #[test]
+    fn simplify_nested_control() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let x = db.makeset();
+        db.insert(Term::Param {
+            start,
+            index: 0,
+            root: x,
+        });
+        let one = db.constant(1);
+        let outer = db.branch(start, one);
+        let taken = db.control_proj(outer, 1);
+        let not_taken = db.control_proj(outer, 0);
+        let inner = db.branch(not_taken, x);
+        let inner_true = db.control_proj(inner, 1);
+        let inner_false = db.control_proj(inner, 0);
+        let two = db.constant(2);
+        let three = db.constant(3);
+        let inner_region = db.region(inner_true, inner_false);
+        let inner_phi = db.phi(inner_region, two, three);
+        let ten = db.constant(10);
+        let region = db.region(taken, inner_region);
+        let phi = db.phi(region, ten, inner_phi);
+        db.finish(region, phi);
+        assert!(db.simplify_control());
+
+        assert_eq!(db.find(region), db.find(start));
+        assert_eq!(db.find(phi), db.find(ten));
+        assert!(
+            !db.canonical_terms()
+                .any(|term| matches!(term, Term::Region { .. } | Term::Phi { .. }))
+        );
+        let dead = db.dead_code_classes();
+        assert!(dead.contains(&db.find(inner)));
+        assert!(dead.contains(&db.find(inner_region)));
+        assert!(!db.simplify_control());
+
+        // Both edges of `same` come from `start`, so only a phi that picks the same value on each
+        // lets it collapse.
+        let mut db = Graph::new(&mut interner);
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let two = db.constant(2);
+        let three = db.constant(3);
+        let same = db.region(start, start);
+        let phi = db.phi(same, two, three);
+        db.finish(same, phi);
+        assert!(!db.simplify_control());
+        assert_ne!(db.find(same), db.find(start));
+
+        let mut db = Graph::new(&mut interner);
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let two = db.constant(2);
+        let same = db.region(start, start);
+        let phi = db.phi(same, two, two);
+        db.finish(same, phi);
+        assert!(db.simplify_control());
+        assert_eq!(db.find(same), db.find(start));
+        assert_eq!(db.find(phi), db.find(two));
+    }
+
+    #[test]
+    fn finish_joins_returns() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let x = db.makeset();
+        db.insert(Term::Param {
+            start,
+            index: 0,
+            root: x,
+        });
+        let branch = db.branch(start, x);
+        let taken = db.control_proj(branch, 1);
+        let not_taken = db.control_proj(branch, 0);
+        let one = db.constant(1);
+        let two = db.constant(2);
+        assert_eq!(db.return_value(), None);
+        db.finish(taken, one);
+        assert_eq!(db.return_value(), Some(one));
+        assert_eq!(db.finish.num_allocated_rows, 1);
+        db.finish(not_taken, two);
+        let finish = db.finish(start, two);
+        assert_eq!(db.region.num_allocated_rows, 2);
+        assert_eq!(db.phi.num_allocated_rows, 2);
+        assert_eq!(db.finish.num_allocated_rows, 1);
+        let ret = db.return_value().unwrap();
+        assert!(db.canonical_terms().any(
+            |term| matches!(term, Term::Finish { value, root, .. } if value == ret && root == finish)
+        ));
+        assert_eq!(db.finish(not_taken, two), finish);
+        assert_eq!(db.return_value(), Some(ret));
+        assert_eq!(db.region.num_allocated_rows, 2);
+        assert_eq!(db.phi.num_allocated_rows, 2);
+        assert_eq!(db.finish.num_allocated_rows, 1);
+    }
+
+    #[test]
+    fn dead_code_classes() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let start = db.makeset();
+        db.insert(Term::Start { root: start });
+        let x = db.makeset();
+        db.insert(Term::Param {
+            start,
+            index: 0,
+            root: x,
+        });
+        let one = db.constant(1);
+        let unused = db.add(x, one);
+        let two = db.constant(2);
+        let used = db.add(x, two);
+        assert_eq!(db.dead_code_classes().len(), 6);
+
+        db.finish(start, used);
+        assert_eq!(db.dead_code_classes(), vec![one, unused]);
+        assert_eq!(db.live_classes().len(), 7);
+    }
+
+    #[test]
+    fn live_classes() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        let mut db = Graph::new(&mut interner);
+
+        let one = db.constant(1);
+        let two = db.constant(2);
+        let dead = db.makeset_many(3);
+        let sum = db.add(one, two);
+        let other = db.add(two, one);
+        db.merge_mut(sum, other);
+        db.rebuild();
+
+        let live = db.live_classes();
+        let sum = db.find(sum);
+        assert_eq!(
+            live.iter().collect::<Vec<_>>(),
+            vec![one.idx() as usize, two.idx() as usize, sum.idx() as usize]
+        );
+        assert!(dead.clone().all(|idx| !live.contains(idx as usize)));
+
+        let kept: Vec<Term> = db
+            .canonical_terms()
+            .filter(|term| {
+                term.operands()
+                    .iter()
+                    .all(|id| live.contains(id.idx() as usize))
+            })
+            .collect();
+        let remap = db.renumber_dense();
+        assert_eq!(db.num_classes() as usize, live.len());
+        assert_eq!(db.live_classes().len(), live.len());
+        for term in kept {
+            assert!(db.contains(term.map_classes(&remap)));
+        }
+        assert!(
+            dead.into_iter()
+                .all(|idx| !remap(ClassId::new(idx)).is_valid())
+        );
+    }
+
+    #[test]
+    fn encode_layout() {
+        let [a, b, c, root] = [3, 7, 11, 42].map(ClassId::new);
+        assert_eq!(
+            constant_encode(&Term::Constant { value: -1, root }),
+            ([u32::MAX], [42])
+        );
+        assert_eq!(
+            constant_encode(&Term::Constant {
+                value: i32::MIN,
+                root
+            }),
+            ([0x80000000], [42])
+        );
+        assert_eq!(
+            param_encode(&Term::Param {
+                start: a,
+                index: 5,
+                root
+            }),
+            ([3, 5], [42])
+        );
+        assert_eq!(start_encode(&Term::Start { root }), ([], [42]));
+        assert_eq!(
+            phi_encode(&Term::Phi {
+                region: a,
+                lhs: b,
+                rhs: c,
+                root
+            }),
+            ([3, 7, 11], [42])
+        );
+        assert_eq!(
+            control_proj_encode(&Term::ControlProj {
+                pred: b,
+                index: 1,
+                root
+            }),
+            ([7, 1], [42])
+        );
+        assert_eq!(
+            less_encode(&Term::Less {
+                lhs: c,
+                rhs: a,
+                root
+            }),
+            ([11, 3], [42])
+        );
+        assert_eq!(opaque_encode(&Term::Opaque { id: 9, root }), ([9], [42]));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn check_control_uniqueness() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn basic(x) { while x { x = x + -1; } return x; } \
+                       fn two(x) { if x { return 1; } else { return 2; } }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        for graph in graphs.iter_mut() {
+            graph.rebuild();
+            assert_eq!(graph.check_control_uniqueness(), Ok(()));
+        }
+
+        let mut graph = graphs[0].clone();
+        let start = graph.makeset();
+        graph.insert(Term::Start { root: start });
+        assert_eq!(graph.check_control_uniqueness(), Ok(()));
+        // A second Start that hash-consing didn't unify with the first, leaving the parameter's
+        // start class orphaned.
+        let start = graph.makeset();
+        graph.start.insert_row(&[], &[start.idx()], |new, _| *new);
+        assert_eq!(
+            graph.check_control_uniqueness(),
+            Err("expected one start class, found 2".to_string())
+        );
+
+        let mut graph = graphs[1].clone();
+        let (pred, _) = graph.return_sites().next().unwrap();
+        let value = graph.constant(3);
+        let root = graph.makeset();
+        graph.insert(Term::Finish { pred, value, root });
+        assert_eq!(
+            graph.check_control_uniqueness(),
+            Err("expected at most one finish class, found 2".to_string())
+        );
+
+        let db = Graph::new(&mut interner);
+        assert!(db.check_control_uniqueness().is_err());
+    }
 }
@@ -0,0 +1,128 @@
+use crate::ast::{BlockAST, ExpressionAST, FunctionAST, ProgramAST, StatementAST};
+
+pub trait Visitor {
+    fn visit_func(&mut self, func: &FunctionAST<'_>) {
+        walk_func(func, self);
+    }
+
+    fn visit_block(&mut self, block: &BlockAST<'_>) {
+        walk_block(block, self);
+    }
+
+    fn visit_stmt(&mut self, stmt: &StatementAST<'_>) {
+        walk_stmt(stmt, self);
+    }
+
+    fn visit_expr(&mut self, expr: &ExpressionAST<'_>) {
+        walk_expr(expr, self);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(program: &ProgramAST<'_>, visitor: &mut V) {
+    for func in program.funcs.as_ref() {
+        visitor.visit_func(func);
+    }
+}
+
+pub fn walk_func<V: Visitor + ?Sized>(func: &FunctionAST<'_>, visitor: &mut V) {
+    visitor.visit_block(&func.block);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(block: &BlockAST<'_>, visitor: &mut V) {
+    for stmt in block.stmts.as_ref() {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(stmt: &StatementAST<'_>, visitor: &mut V) {
+    use StatementAST::*;
+    match stmt {
+        Block(block) => visitor.visit_block(block),
+        Assign(_, expr) | Return(expr) => visitor.visit_expr(expr),
+        IfElse(cond, lhs, rhs) => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(lhs);
+            if let Some(rhs) = rhs {
+                visitor.visit_block(rhs);
+            }
+        }
+        While(cond, body) => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(body);
+        }
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(expr: &ExpressionAST<'_>, visitor: &mut V) {
+    use ExpressionAST::*;
+    match expr {
+        NumberLiteral(_) | Variable(_) => {}
+        Call(_, args) => {
+            for arg in args.as_ref() {
+                visitor.visit_expr(arg);
+            }
+        }
+        Add(lhs, rhs)
+        | Subtract(lhs, rhs)
+        | Multiply(lhs, rhs)
+        | Divide(lhs, rhs)
+        | Modulo(lhs, rhs)
+        | EqualsEquals(lhs, rhs)
+        | NotEquals(lhs, rhs)
+        | Less(lhs, rhs)
+        | LessEquals(lhs, rhs)
+        | Greater(lhs, rhs)
+        | GreaterEquals(lhs, rhs) => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        BlockExpr(block, value) => {
+            visitor.visit_block(block);
+            visitor.visit_expr(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use util::arena::Arena;
+    use util::interner::StringInterner;
+
+    use crate::ast::tests::get_example_imp_programs;
+    use crate::grammar::ProgramParser;
+
+    struct AssignCounter {
+        count: usize,
+    }
+
+    impl Visitor for AssignCounter {
+        fn visit_stmt(&mut self, stmt: &StatementAST<'_>) {
+            if let StatementAST::Assign(..) = stmt {
+                self.count += 1;
+            }
+            walk_stmt(stmt, self);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn count_assigns() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let mut counter = AssignCounter { count: 0 };
+        for program in get_example_imp_programs() {
+            let program = ProgramParser::new()
+                .parse(&arena, &mut interner, &program)
+                .unwrap();
+            walk_program(&program, &mut counter);
+        }
+        assert_eq!(counter.count, 16);
+    }
+}
@@ -0,0 +1,294 @@
+use core::fmt::{Result, Write};
+use std::collections::BTreeMap;
+
+use db::rebuild::ENode;
+use util::union_find::ClassId;
+
+use crate::ssa::{Graph, Term};
+
+impl Graph {
+    pub fn to_dot(&self) -> String {
+        dot(self)
+    }
+
+    pub fn to_sea_of_nodes_json(&self) -> String {
+        sea_of_nodes_json(self)
+    }
+}
+
+pub fn dot(graph: &Graph) -> String {
+    let mut s = String::new();
+    dot_to_writer(graph, &mut s).unwrap();
+    s
+}
+
+pub fn dot_to_writer<W: Write>(graph: &Graph, w: &mut W) -> Result {
+    writeln!(w, "digraph EGraph {{\ncompound=true")?;
+    let open = |w: &mut W, id: ClassId| {
+        writeln!(
+            w,
+            "subgraph cluster_{} {{\nlabel = \"{}\"\nnode_{} [shape=point style=invis]",
+            id.idx(),
+            id.idx(),
+            id.idx()
+        )
+    };
+    let node = |w: &mut W, name: &str, label: &str, control: bool| {
+        if control {
+            writeln!(w, "{} [label=\"{}\" shape=box]", name, label)
+        } else {
+            writeln!(w, "{} [label=\"{}\"]", name, label)
+        }
+    };
+    let link = |w: &mut W, src: &str, dst: ClassId| {
+        writeln!(
+            w,
+            "node_{} -> {} [ltail=\"cluster_{}\"]",
+            dst.idx(),
+            src,
+            dst.idx()
+        )
+    };
+    let close = |w: &mut W| writeln!(w, "}}");
+    for term in graph.terms() {
+        use Term::*;
+        let name = match term {
+            Constant { value, .. } => format!("cons_{}", value as u32),
+            Param { start, index, .. } => format!("param_{}_{}", start.idx(), index),
+            Start { .. } => "start".to_string(),
+            Region { lhs, rhs, .. } => format!("region_{}_{}", lhs.idx(), rhs.idx()),
+            Branch { pred, cond, .. } => format!("branch_{}_{}", pred.idx(), cond.idx()),
+            ControlProj { pred, index, .. } => format!("control_proj_{}_{}", pred.idx(), index),
+            Finish { pred, value, .. } => format!("finish_{}_{}", pred.idx(), value.idx()),
+            Phi {
+                region, lhs, rhs, ..
+            } => format!("phi_{}_{}_{}", region.idx(), lhs.idx(), rhs.idx()),
+            Add { lhs, rhs, .. } => format!("add_{}_{}", lhs.idx(), rhs.idx()),
+            Subtract { lhs, rhs, .. } => format!("subtract_{}_{}", lhs.idx(), rhs.idx()),
+            Multiply { lhs, rhs, .. } => format!("multiply_{}_{}", lhs.idx(), rhs.idx()),
+            Divide { lhs, rhs, .. } => format!("divide_{}_{}", lhs.idx(), rhs.idx()),
+            Equals { lhs, rhs, .. } => format!("equals_{}_{}", lhs.idx(), rhs.idx()),
+            NotEquals { lhs, rhs, .. } => format!("not_equals_{}_{}", lhs.idx(), rhs.idx()),
+            Less { lhs, rhs, .. } => format!("less_{}_{}", lhs.idx(), rhs.idx()),
+            LessEquals { lhs, rhs, .. } => format!("less_equals_{}_{}", lhs.idx(), rhs.idx()),
+            Opaque { id, .. } => format!("opaque_{}", id),
+        };
+        let label = match term {
+            Constant { value, .. } => format!("{}", value),
+            Param { index, .. } => format!("Param #{}", index),
+            Start { .. } => "Start".to_string(),
+            Region { .. } => "Region".to_string(),
+            Branch { .. } => "Branch".to_string(),
+            ControlProj { index, .. } => format!("π({})", index),
+            Finish { .. } => "Finish".to_string(),
+            Phi { .. } => "ϕ".to_string(),
+            Add { .. } => "+".to_string(),
+            Subtract { .. } => "-".to_string(),
+            Multiply { .. } => "*".to_string(),
+            Divide { .. } => "/".to_string(),
+            Equals { .. } => "==".to_string(),
+            NotEquals { .. } => "!=".to_string(),
+            Less { .. } => "<".to_string(),
+            LessEquals { .. } => "<=".to_string(),
+            Opaque { .. } => "?".to_string(),
+        };
+        let root = term.root();
+        open(w, root)?;
+        node(w, &name, &label, term.is_control())?;
+        close(w)?;
+        match term {
+            Constant { .. } | Start { .. } | Opaque { .. } => {}
+            Param { start, .. } => {
+                link(w, &name, start)?;
+            }
+            Branch { pred, cond, .. } => {
+                link(w, &name, pred)?;
+                link(w, &name, cond)?;
+            }
+            ControlProj { pred, .. } => {
+                link(w, &name, pred)?;
+            }
+            Finish { pred, value, .. } => {
+                link(w, &name, pred)?;
+                link(w, &name, value)?;
+            }
+            Region { lhs, rhs, .. }
+            | Add { lhs, rhs, .. }
+            | Subtract { lhs, rhs, .. }
+            | Multiply { lhs, rhs, .. }
+            | Divide { lhs, rhs, .. }
+            | Equals { lhs, rhs, .. }
+            | NotEquals { lhs, rhs, .. }
+            | Less { lhs, rhs, .. }
+            | LessEquals { lhs, rhs, .. } => {
+                link(w, &name, lhs)?;
+                link(w, &name, rhs)?;
+            }
+            Phi {
+                region, lhs, rhs, ..
+            } => {
+                link(w, &name, region)?;
+                link(w, &name, lhs)?;
+                link(w, &name, rhs)?;
+            }
+        }
+    }
+    close(w)
+}
+
+pub fn sea_of_nodes_json(graph: &Graph) -> String {
+    let mut classes: BTreeMap<ClassId, Vec<String>> = BTreeMap::new();
+    for term in graph.canonical_terms() {
+        use Term::*;
+        let (op, imm, value, control) = match term {
+            Constant { value, .. } => ("constant", Some(value as i64), vec![], vec![]),
+            Param { start, index, .. } => ("param", Some(index as i64), vec![], vec![start]),
+            Start { .. } => ("start", None, vec![], vec![]),
+            Region { lhs, rhs, .. } => ("region", None, vec![], vec![lhs, rhs]),
+            Branch { pred, cond, .. } => ("branch", None, vec![cond], vec![pred]),
+            ControlProj { pred, index, .. } => {
+                ("control_proj", Some(index as i64), vec![], vec![pred])
+            }
+            Finish { pred, value, .. } => ("finish", None, vec![value], vec![pred]),
+            Phi {
+                region, lhs, rhs, ..
+            } => ("phi", None, vec![lhs, rhs], vec![region]),
+            Add { lhs, rhs, .. } => ("add", None, vec![lhs, rhs], vec![]),
+            Subtract { lhs, rhs, .. } => ("subtract", None, vec![lhs, rhs], vec![]),
+            Multiply { lhs, rhs, .. } => ("multiply", None, vec![lhs, rhs], vec![]),
+            Divide { lhs, rhs, .. } => ("divide", None, vec![lhs, rhs], vec![]),
+            Equals { lhs, rhs, .. } => ("equals", None, vec![lhs, rhs], vec![]),
+            NotEquals { lhs, rhs, .. } => ("not_equals", None, vec![lhs, rhs], vec![]),
+            Less { lhs, rhs, .. } => ("less", None, vec![lhs, rhs], vec![]),
+            LessEquals { lhs, rhs, .. } => ("less_equals", None, vec![lhs, rhs], vec![]),
+            Opaque { id, .. } => ("opaque", Some(id as i64), vec![], vec![]),
+        };
+        let ids = |ids: Vec<ClassId>| {
+            ids.iter()
+                .map(|id| id.idx().to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let imm = imm.map_or(String::new(), |imm| format!(",\"imm\":{}", imm));
+        classes.entry(term.root()).or_default().push(format!(
+            "{{\"op\":\"{}\"{},\"value_inputs\":[{}],\"control_inputs\":[{}]}}",
+            op,
+            imm,
+            ids(value),
+            ids(control)
+        ));
+    }
+    let nodes: Vec<String> = classes
+        .into_iter()
+        .map(|(id, terms)| format!("\"{}\":[{}]", id.idx(), terms.join(",")))
+        .collect();
+    format!("{{\"nodes\":{{{}}}}}", nodes.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use util::arena::Arena;
+    use util::interner::StringInterner;
+
+    use crate::ai::abstract_interpret;
+    use crate::grammar::ProgramParser;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn small_dot() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut graph = Graph::new(&mut interner);
+        let x = graph.constant(1);
+        let y = graph.constant(2);
+        let sum = graph.add(x, y);
+        let start = graph.makeset();
+        graph.insert(Term::Start { root: start });
+        let (x, y, sum) = (x.idx(), y.idx(), sum.idx());
+
+        let s = graph.to_dot();
+        assert!(s.starts_with("digraph EGraph {\ncompound=true\n"));
+        assert!(s.contains(&format!(
+            "subgraph cluster_{} {{\nlabel = \"{}\"\nnode_{} [shape=point style=invis]\nadd_{}_{} [label=\"+\"]\n}}\n",
+            sum, sum, sum, x, y
+        )));
+        assert!(s.contains(&format!(
+            "subgraph cluster_{} {{\nlabel = \"{}\"\nnode_{} [shape=point style=invis]\ncons_1 [label=\"1\"]\n}}\n",
+            x, x, x
+        )));
+        assert!(s.contains("start [label=\"Start\" shape=box]\n"));
+        assert!(s.contains(&format!(
+            "node_{} -> add_{}_{} [ltail=\"cluster_{}\"]\n",
+            x, x, y, x
+        )));
+        assert!(s.contains(&format!(
+            "node_{} -> add_{}_{} [ltail=\"cluster_{}\"]\n",
+            y, x, y, y
+        )));
+        assert_eq!(s.matches(" -> ").count(), 2);
+        assert!(s.ends_with("}\n"));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn large_dot() {
+        let mut buf: [u64; 100] = [0; 100];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+
+        let mut graph = Graph::new(&mut interner);
+        let mut sum = graph.constant(0);
+        for value in 1..10000 {
+            let constant = graph.constant(value);
+            sum = graph.add(sum, constant);
+        }
+
+        let s = dot(&graph);
+        assert!(s.starts_with("digraph EGraph {\ncompound=true\nsubgraph cluster_0 {\n"));
+        assert!(s.ends_with("}\n"));
+        assert_eq!(s.matches("subgraph cluster_").count(), 19999);
+        assert_eq!(s.matches(" -> ").count(), 19998);
+
+        let mut streamed = String::new();
+        dot_to_writer(&graph, &mut streamed).unwrap();
+        assert_eq!(s, streamed);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn sea_of_nodes_json() {
+        let mut string_buf: [u8; 100] = [0; 100];
+        let string_arena = Arena::new_backed(&mut string_buf);
+        let mut interner = StringInterner::new(&string_arena);
+        let mut buf: [u8; 10000] = [0; 10000];
+        let arena = Arena::new_backed(&mut buf);
+
+        let program = "fn basic(x) { while x { x = x + -1; } return x; }";
+        let program = ProgramParser::new()
+            .parse(&arena, &mut interner, program)
+            .unwrap();
+        let mut graphs = abstract_interpret(&program, &mut interner).unwrap();
+        graphs[0].rebuild();
+        let json = graphs[0].to_sea_of_nodes_json();
+        assert!(json.starts_with(
+            "{\"nodes\":{\"0\":[{\"op\":\"start\",\"value_inputs\":[],\"control_inputs\":[]}],"
+        ));
+        assert!(json.contains(
+            "\"1\":[{\"op\":\"param\",\"imm\":0,\"value_inputs\":[],\"control_inputs\":[0]}]"
+        ));
+        assert!(json.contains("{\"op\":\"region\",\"value_inputs\":[],\"control_inputs\":[0,4]}"));
+        assert!(json.contains("{\"op\":\"branch\",\"value_inputs\":[1],\"control_inputs\":[2]}"));
+        assert!(json.contains(
+            "{\"op\":\"control_proj\",\"imm\":1,\"value_inputs\":[],\"control_inputs\":[3]}"
+        ));
+        assert!(json.contains("{\"op\":\"finish\",\"value_inputs\":[8],\"control_inputs\":[12]}"));
+        assert!(json.contains("{\"op\":\"phi\",\"value_inputs\":[1,7],\"control_inputs\":[2]}"));
+        assert!(json.contains("{\"op\":\"add\",\"value_inputs\":[8,6],\"control_inputs\":[]}"));
+        assert!(json.contains("\"6\":[{\"op\":\"constant\",\"imm\":-1,"));
+        assert!(json.ends_with("]}}"));
+    }
+}
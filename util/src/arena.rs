@@ -8,6 +8,7 @@ use core::slice;
 use core::sync::atomic::{AtomicUsize, Ordering};
 use libc::{
     MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE, mmap, mprotect,
+    munmap,
 };
 
 pub(crate) const MCOMMIT_GRANULARITY: usize = 1 << 20;
@@ -48,10 +49,7 @@ impl<T> Hash for BrandedArenaId<T> {
 
 impl<T> Clone for BrandedArenaId<T> {
     fn clone(&self) -> Self {
-        BrandedArenaId {
-            id: self.id,
-            _phantom: PhantomData,
-        }
+        *self
     }
 }
 
@@ -105,7 +103,14 @@ impl<'a> ArenaInternal<'a> {
         }
     }
 
-    pub(crate) fn realign<'b>(&'b mut self, align: usize) {
+    #[cfg_attr(miri, allow(dead_code))]
+    pub(crate) unsafe fn unmap(&self) {
+        assert!(self.virt, "unmapped backed arena");
+        let code = unsafe { munmap(self.ptr as _, self.max) };
+        assert_eq!(code, 0, "munmap failed in arena");
+    }
+
+    pub(crate) fn realign(&mut self, align: usize) {
         if align > 1 {
             #[allow(unused_assignments)]
             let mut aligned_offset = 0;
@@ -116,7 +121,7 @@ impl<'a> ArenaInternal<'a> {
         }
     }
 
-    pub(crate) fn alloc<'b>(&'b self, size: usize, align: Option<usize>) -> usize {
+    pub(crate) fn alloc(&self, size: usize, align: Option<usize>) -> usize {
         #[allow(unused_assignments)]
         let mut begin_offset = 0;
         let mut old_offset = self.offset.load(Ordering::Relaxed);
@@ -153,7 +158,8 @@ impl<'a> ArenaInternal<'a> {
         begin_offset
     }
 
-    pub(crate) unsafe fn alloc_assume_aligned<'b>(&'b self, size: usize) -> usize {
+    #[cfg_attr(miri, allow(dead_code))]
+    pub(crate) unsafe fn alloc_assume_aligned(&self, size: usize) -> usize {
         let old_offset = self.offset.fetch_add(size, Ordering::Relaxed);
         assert!(old_offset + size <= self.max, "ran out of space in arena");
         if self.virt {
@@ -166,9 +172,8 @@ impl<'a> ArenaInternal<'a> {
 }
 
 unsafe fn commit_sections(ptr: *mut u8, old_offset: usize, new_offset: usize) {
-    let prev_first_uncommited_section =
-        (old_offset + MCOMMIT_GRANULARITY - 1) / MCOMMIT_GRANULARITY;
-    let new_first_uncommited_section = (new_offset + MCOMMIT_GRANULARITY - 1) / MCOMMIT_GRANULARITY;
+    let prev_first_uncommited_section = old_offset.div_ceil(MCOMMIT_GRANULARITY);
+    let new_first_uncommited_section = new_offset.div_ceil(MCOMMIT_GRANULARITY);
     let num_sections = new_first_uncommited_section - prev_first_uncommited_section;
     if num_sections != 0 {
         let code = unsafe {
@@ -195,7 +200,17 @@ impl<'a> Arena<'a> {
         }
     }
 
-    pub fn new<'b, T>(&'b self, x: T) -> &'a mut T {
+    /// Runs `f` with this arena and then frees everything `f` allocated. Allocations made inside
+    /// `f` can't escape it, since their lifetime is local to the closure.
+    pub fn scope<R>(&mut self, f: impl FnOnce(&Arena<'_>) -> R) -> R {
+        let offset = self.arena.offset.load(Ordering::Relaxed);
+        let result = f(self);
+        self.arena.offset.store(offset, Ordering::Relaxed);
+        result
+    }
+
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<T>(&self, x: T) -> &'a mut T {
         const {
             assert!(!needs_drop::<T>());
         }
@@ -237,7 +252,7 @@ impl<'a> Arena<'a> {
         }
     }
 
-    pub fn alloc<'b, T>(&'b self, x: T) -> BrandedArenaId<T> {
+    pub fn alloc<T>(&self, x: T) -> BrandedArenaId<T> {
         const {
             assert!(!needs_drop::<T>());
         }
@@ -415,6 +430,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn scoped_backed_arena() {
+        let mut buf: [u64; 3] = [0; 3];
+        let mut arena = Arena::new_backed(&mut buf);
+        let x = arena.new::<u32>(1);
+        for i in 0..8 {
+            let sum = arena.scope(|arena| *arena.new::<u64>(i) + *arena.new::<u32>(2) as u64);
+            assert_eq!(sum, i + 2);
+        }
+        assert_eq!(*x, 1);
+        assert_eq!(arena.arena.offset.load(Ordering::Relaxed), 4);
+    }
+
     #[test]
     fn modify_backed_arena() {
         let mut buf: [u64; 1] = [0; 1];
@@ -472,14 +500,14 @@ mod tests {
         for _ in 0..(1 << 20) {
             ids.push(arena.alloc::<i128>(42));
         }
-        for i in 0..(1 << 20) {
-            assert_eq!(*arena.get(ids[i]), 42);
+        for id in &ids {
+            assert_eq!(*arena.get(*id), 42);
         }
-        for i in 0..(1 << 20) {
-            *arena.get_mut(ids[i]) = i as i128;
+        for (i, id) in ids.iter().enumerate() {
+            *arena.get_mut(*id) = i as i128;
         }
-        for i in 0..(1 << 20) {
-            assert_eq!(*arena.get(ids[i]), i as i128);
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(*arena.get(*id), i as i128);
         }
     }
 
@@ -0,0 +1,127 @@
+use alloc::vec::Vec;
+
+#[derive(Clone, Debug, Default)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self { words: Vec::new() }
+    }
+
+    pub fn with_capacity(bits: usize) -> Self {
+        Self {
+            words: Vec::with_capacity(bits.div_ceil(64)),
+        }
+    }
+
+    pub fn insert(&mut self, idx: usize) -> bool {
+        let (word, bit) = (idx / 64, idx % 64);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let old = self.words[word];
+        self.words[word] = old | (1 << bit);
+        old & (1 << bit) == 0
+    }
+
+    pub fn remove(&mut self, idx: usize) -> bool {
+        let (word, bit) = (idx / 64, idx % 64);
+        let Some(word) = self.words.get_mut(word) else {
+            return false;
+        };
+        let old = *word;
+        *word = old & !(1 << bit);
+        old & (1 << bit) != 0
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        self.words
+            .get(idx / 64)
+            .is_some_and(|word| word & (1 << (idx % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(word_idx, word)| {
+            let mut word = *word;
+            core::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(word_idx * 64 + bit)
+            })
+        })
+    }
+}
+
+impl Extend<usize> for BitSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for idx in iter {
+            self.insert(idx);
+        }
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_get_iter() {
+        let mut set = BitSet::new();
+        assert!(set.is_empty());
+        assert!(!set.contains(0));
+        assert!(!set.contains(1000));
+        assert!(set.insert(3));
+        assert!(set.insert(64));
+        assert!(set.insert(200));
+        assert!(!set.insert(64));
+        assert!(set.contains(3));
+        assert!(set.contains(64));
+        assert!(set.contains(200));
+        assert!(!set.contains(63));
+        assert!(!set.contains(65));
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 64, 200]);
+
+        assert!(set.remove(64));
+        assert!(!set.remove(64));
+        assert!(!set.remove(5000));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 200]);
+
+        let other: BitSet = [200, 3].into_iter().collect();
+        assert_eq!(
+            other.iter().collect::<Vec<_>>(),
+            set.iter().collect::<Vec<_>>()
+        );
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.iter().next(), None);
+    }
+}
@@ -1,4 +1,13 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
 use core::hash::Hash;
+use core::mem::take;
+
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
 
 use crate::arena::{Arena, BrandedArenaId};
@@ -13,40 +22,99 @@ impl IdentifierId {
     }
 }
 
+/// Interns `str`s through a `BytesInterner`, so strings and byte strings share one
+/// implementation.
 #[derive(Debug)]
 pub struct StringInterner<'a, 'b> {
-    str_to_id: HashMap<&'a str, IdentifierId>,
-    id_to_str: Vec<&'a str>,
-    arena: &'b Arena<'a>,
+    bytes: BytesInterner<'a, 'b>,
+    scratch: String,
 }
 
 impl<'a, 'b> StringInterner<'a, 'b> {
     pub fn new(arena: &'b Arena<'a>) -> Self {
         Self {
-            str_to_id: HashMap::new(),
-            id_to_str: vec![],
-            arena,
+            bytes: BytesInterner::new(arena),
+            scratch: String::new(),
+        }
+    }
+
+    /// `capacity` is the expected number of distinct identifiers. Interning up to that many
+    /// neither rehashes nor reallocates.
+    pub fn with_capacity(arena: &'b Arena<'a>, capacity: usize) -> Self {
+        Self {
+            bytes: BytesInterner::with_capacity(arena, capacity),
+            scratch: String::new(),
         }
     }
 
     pub fn intern(&mut self, string: &str) -> IdentifierId {
-        if let Some(id) = self.str_to_id.get(string) {
+        self.bytes.intern(string.as_bytes())
+    }
+
+    pub fn intern_fmt(&mut self, args: fmt::Arguments<'_>) -> IdentifierId {
+        if let Some(string) = args.as_str() {
+            return self.intern(string);
+        }
+        let mut scratch = take(&mut self.scratch);
+        scratch.clear();
+        scratch.write_fmt(args).unwrap();
+        let id = self.intern(&scratch);
+        self.scratch = scratch;
+        id
+    }
+
+    pub fn get(&self, id: IdentifierId) -> &'a str {
+        // SAFETY: only `intern` adds to `bytes`, and it only adds the bytes of `str`s.
+        unsafe { str::from_utf8_unchecked(self.bytes.get(id)) }
+    }
+
+    pub fn num_idens(&self) -> usize {
+        self.bytes.num_idens()
+    }
+}
+
+#[derive(Debug)]
+pub struct BytesInterner<'a, 'b> {
+    bytes_to_id: HashMap<&'a [u8], IdentifierId>,
+    id_to_bytes: Vec<&'a [u8]>,
+    arena: &'b Arena<'a>,
+}
+
+impl<'a, 'b> BytesInterner<'a, 'b> {
+    pub fn new(arena: &'b Arena<'a>) -> Self {
+        Self {
+            bytes_to_id: HashMap::new(),
+            id_to_bytes: vec![],
+            arena,
+        }
+    }
+
+    pub fn with_capacity(arena: &'b Arena<'a>, capacity: usize) -> Self {
+        Self {
+            bytes_to_id: HashMap::with_capacity(capacity),
+            id_to_bytes: Vec::with_capacity(capacity),
+            arena,
+        }
+    }
+
+    pub fn intern(&mut self, bytes: &[u8]) -> IdentifierId {
+        if let Some(id) = self.bytes_to_id.get(bytes) {
             *id
         } else {
-            let in_arena = self.arena.new_ref(string);
-            let id = IdentifierId(self.id_to_str.len().try_into().unwrap());
-            self.str_to_id.insert(in_arena, id);
-            self.id_to_str.push(in_arena);
+            let in_arena = self.arena.new_ref(bytes);
+            let id = IdentifierId(self.id_to_bytes.len().try_into().unwrap());
+            self.bytes_to_id.insert(in_arena, id);
+            self.id_to_bytes.push(in_arena);
             id
         }
     }
 
-    pub fn get(&self, id: IdentifierId) -> &'a str {
-        self.id_to_str[id.0 as usize]
+    pub fn get(&self, id: IdentifierId) -> &'a [u8] {
+        self.id_to_bytes[id.0 as usize]
     }
 
     pub fn num_idens(&self) -> usize {
-        self.id_to_str.len()
+        self.id_to_bytes.len()
     }
 }
 
@@ -66,6 +134,16 @@ impl<'a, 'b, T: Eq + Hash> Interner<'a, 'b, T> {
         }
     }
 
+    /// `capacity` is the expected number of distinct objects, as for
+    /// [`StringInterner::with_capacity`].
+    pub fn with_capacity(arena: &'b Arena<'a>, capacity: usize) -> Self {
+        Self {
+            obj_to_id: HashMap::with_capacity(capacity),
+            id_to_obj: Vec::with_capacity(capacity),
+            arena,
+        }
+    }
+
     pub fn intern(&mut self, obj: T) -> BrandedArenaId<T> {
         if let Some(id) = self.obj_to_id.get(&obj) {
             *id
@@ -112,6 +190,49 @@ mod tests {
         assert_eq!(interner.get(id4), str2);
     }
 
+    #[test]
+    fn intern_formatted() {
+        let mut buf: [u64; 8] = [0; 8];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = StringInterner::new(&arena);
+        // Non-literal arguments, so the format strings aren't folded into a constant.
+        let (five, one, two) = (5, 1, 2);
+        let id1 = interner.intern_fmt(format_args!("cons_{}", five));
+        let id2 = interner.intern_fmt(format_args!("region_{}_{}", one, two));
+        assert_ne!(id1, id2);
+        assert_eq!(interner.get(id1), "cons_5");
+        assert_eq!(interner.get(id2), "region_1_2");
+        assert_eq!(interner.intern_fmt(format_args!("cons_{}", five)), id1);
+        assert_eq!(interner.intern("cons_5"), id1);
+        assert_eq!(
+            interner.intern_fmt(format_args!("plain")),
+            interner.intern("plain")
+        );
+        assert_eq!(interner.num_idens(), 3);
+    }
+
+    #[test]
+    fn intern_bytes() {
+        let mut buf: [u64; 4] = [0; 4];
+        let arena = Arena::new_backed(&mut buf);
+        let mut interner = BytesInterner::new(&arena);
+        let bytes1: &[u8] = &[0xff, 0xfe, 0x00];
+        let bytes2: &[u8] = b"valid utf-8";
+        let id1 = interner.intern(bytes1);
+        let id2 = interner.intern(bytes2);
+        assert_ne!(id1, id2);
+        let id3 = interner.intern(&[0xff, 0xfe, 0x00]);
+        let id4 = interner.intern(bytes2);
+        let id5 = interner.intern(&[]);
+        assert_eq!(id1, id3);
+        assert_eq!(id2, id4);
+        assert_ne!(id5, id1);
+        assert_eq!(interner.get(id1), bytes1);
+        assert_eq!(interner.get(id2), bytes2);
+        assert_eq!(interner.get(id5), &[]);
+        assert_eq!(interner.num_idens(), 3);
+    }
+
     #[test]
     fn intern_objs() {
         let mut buf: [u64; 4] = [0; 4];
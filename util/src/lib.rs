@@ -1,6 +1,13 @@
-#![feature(clone_to_uninit, negative_impls, ptr_metadata)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![feature(clone_to_uninit, ptr_metadata)]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "hashbrown")))]
+compile_error!("util needs the `hashbrown` feature when built without `std`");
 
 pub mod arena;
+pub mod bitset;
 pub mod interner;
 pub mod union_find;
 pub mod vec;
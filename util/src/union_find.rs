@@ -1,10 +1,17 @@
+use alloc::vec;
+use alloc::vec::Vec;
 use core::cell::Cell;
+use core::cmp::Ordering;
+use core::mem::take;
+use core::ops::Range;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
 pub struct ClassId(u32);
 
 impl ClassId {
+    pub const INVALID: ClassId = ClassId(u32::MAX);
+
     pub fn new(idx: u32) -> Self {
         Self(idx)
     }
@@ -12,42 +19,101 @@ impl ClassId {
     pub fn idx(&self) -> u32 {
         self.0
     }
+
+    pub fn is_valid(&self) -> bool {
+        *self != Self::INVALID
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RepresentativePolicy {
+    #[default]
+    MinId,
+    ByRank,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct UnionFind {
     vec: Vec<Cell<ClassId>>,
+    ranks: Vec<Cell<u32>>,
+    policy: RepresentativePolicy,
+}
+
+impl Default for UnionFind {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UnionFind {
     pub fn new() -> Self {
-        Self { vec: Vec::new() }
+        Self::new_with_policy(RepresentativePolicy::MinId)
+    }
+
+    pub fn new_with_policy(policy: RepresentativePolicy) -> Self {
+        Self {
+            vec: Vec::new(),
+            ranks: Vec::new(),
+            policy,
+        }
     }
 
     pub fn new_all_not_equals(amount: u32) -> Self {
         Self {
             vec: (0..amount).map(|idx| Cell::new(ClassId(idx))).collect(),
+            ..Self::new()
         }
     }
 
     pub fn new_all_equals(amount: u32) -> Self {
         Self {
             vec: vec![Cell::new(ClassId(0)); amount as usize],
+            ..Self::new()
+        }
+    }
+
+    pub fn from_pairs(amount: u32, pairs: &[(ClassId, ClassId)]) -> Self {
+        let uf = Self::new_all_not_equals(amount);
+        for (x, y) in pairs {
+            uf.merge(*x, *y);
         }
+        uf
+    }
+
+    pub fn policy(&self) -> RepresentativePolicy {
+        self.policy
     }
 
     pub fn makeset(&mut self) -> ClassId {
         let len = self.vec.len();
         let id = ClassId(len.try_into().unwrap());
+        assert!(id.is_valid());
         self.vec.push(Cell::new(id));
+        if self.policy == RepresentativePolicy::ByRank {
+            self.ranks.push(Cell::new(0));
+        }
         id
     }
 
+    pub fn makeset_many(&mut self, n: u32) -> Range<u32> {
+        let start = self.num_classes();
+        let end = start.checked_add(n).unwrap();
+        assert!(end == start || ClassId(end - 1).is_valid());
+        self.vec.reserve(n as usize);
+        self.vec
+            .extend((start..end).map(|idx| Cell::new(ClassId(idx))));
+        if self.policy == RepresentativePolicy::ByRank {
+            self.ranks.resize(end as usize, Cell::new(0));
+        }
+        start..end
+    }
+
     pub fn num_classes(&self) -> u32 {
         self.vec.len().try_into().unwrap()
     }
 
     pub fn find(&self, mut id: ClassId) -> ClassId {
+        debug_assert!(id.is_valid(), "find on invalid class");
         while id != self.parent(id) {
             self.set_parent(id, self.parent(self.parent(id)));
             id = self.parent(id);
@@ -55,6 +121,47 @@ impl UnionFind {
         id
     }
 
+    /// Makes each of `ids` its own class again. Every member of a class being split must be
+    /// listed, or the rest keep pointing into it.
+    pub fn reset(&self, ids: &[ClassId]) {
+        for id in ids {
+            self.set_parent(*id, *id);
+            if let Some(rank) = self.ranks.get(id.0 as usize) {
+                rank.set(0);
+            }
+        }
+    }
+
+    pub fn merge_all(&self, ids: &[ClassId]) -> Option<ClassId> {
+        let (first, rest) = ids.split_first()?;
+        Some(
+            rest.iter()
+                .fold(self.find(*first), |rep, id| self.merge(rep, *id)),
+        )
+    }
+
+    pub fn find_no_compress(&self, mut id: ClassId) -> ClassId {
+        debug_assert!(id.is_valid(), "find on invalid class");
+        while id != self.parent(id) {
+            id = self.parent(id);
+        }
+        id
+    }
+
+    pub fn depth(&self, mut id: ClassId) -> u32 {
+        debug_assert!(id.is_valid(), "depth on invalid class");
+        let mut depth = 0;
+        while id != self.parent(id) {
+            id = self.parent(id);
+            depth += 1;
+        }
+        depth
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ClassId, ClassId)> + '_ {
+        (0..self.num_classes()).map(|idx| (ClassId(idx), self.find_no_compress(ClassId(idx))))
+    }
+
     #[inline]
     fn parent(&self, id: ClassId) -> ClassId {
         self.vec[id.0 as usize].get()
@@ -66,6 +173,10 @@ impl UnionFind {
     }
 
     pub fn merge(&self, mut x: ClassId, mut y: ClassId) -> ClassId {
+        debug_assert!(x.is_valid() && y.is_valid(), "merge on invalid class");
+        if self.policy == RepresentativePolicy::ByRank {
+            return self.merge_by_rank(x, y);
+        }
         while self.parent(x) != self.parent(y) {
             if self.parent(x) > self.parent(y) {
                 if x == self.parent(x) {
@@ -87,6 +198,94 @@ impl UnionFind {
         }
         self.parent(x)
     }
+
+    fn merge_by_rank(&self, x: ClassId, y: ClassId) -> ClassId {
+        let x = self.find(x);
+        let y = self.find(y);
+        if x == y {
+            return x;
+        }
+        let x_rank = &self.ranks[x.0 as usize];
+        let y_rank = &self.ranks[y.0 as usize];
+        let (child, rep) = match x_rank.get().cmp(&y_rank.get()) {
+            Ordering::Less => (x, y),
+            Ordering::Greater => (y, x),
+            Ordering::Equal => {
+                x_rank.set(x_rank.get() + 1);
+                (y, x)
+            }
+        };
+        self.set_parent(child, rep);
+        rep
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExplicitUnionFind {
+    reps: Vec<ClassId>,
+    members: Vec<Vec<ClassId>>,
+}
+
+impl ExplicitUnionFind {
+    pub fn new() -> Self {
+        Self {
+            reps: Vec::new(),
+            members: Vec::new(),
+        }
+    }
+
+    pub fn makeset(&mut self) -> ClassId {
+        let id = ClassId(self.reps.len().try_into().unwrap());
+        assert!(id.is_valid());
+        self.reps.push(id);
+        self.members.push(vec![id]);
+        id
+    }
+
+    pub fn num_classes(&self) -> u32 {
+        self.reps.len().try_into().unwrap()
+    }
+
+    pub fn find(&self, id: ClassId) -> ClassId {
+        debug_assert!(id.is_valid(), "find on invalid class");
+        self.reps[id.0 as usize]
+    }
+
+    pub fn members(&self, id: ClassId) -> &[ClassId] {
+        &self.members[self.find(id).0 as usize]
+    }
+
+    pub fn merge(&mut self, x: ClassId, y: ClassId) -> ClassId {
+        debug_assert!(x.is_valid() && y.is_valid(), "merge on invalid class");
+        let x = self.find(x);
+        let y = self.find(y);
+        if x == y {
+            return x;
+        }
+        let (rep, other) = if x < y { (x, y) } else { (y, x) };
+        let moved = take(&mut self.members[other.0 as usize]);
+        for id in &moved {
+            self.reps[id.0 as usize] = rep;
+        }
+        self.members[rep.0 as usize].extend(moved);
+        rep
+    }
+
+    pub fn split_off(&mut self, id: ClassId) -> ClassId {
+        let rep = self.find(id);
+        let mut remaining = take(&mut self.members[rep.0 as usize]);
+        remaining.retain(|member| *member != id);
+        self.reps[id.0 as usize] = id;
+        self.members[id.0 as usize] = vec![id];
+        let Some(new_rep) = remaining.iter().min().copied() else {
+            return id;
+        };
+        for member in &remaining {
+            self.reps[member.0 as usize] = new_rep;
+        }
+        self.members[new_rep.0 as usize] = remaining;
+        new_rep
+    }
 }
 
 #[cfg(test)]
@@ -142,4 +341,209 @@ mod tests {
             assert_eq!(uf.find(ids[i]), uf.find(ids[999]));
         }
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn iter_uf() {
+        let mut uf = UnionFind::new();
+        let ids: Vec<_> = (0..5).map(|_| uf.makeset()).collect();
+        uf.merge(ids[3], ids[1]);
+        uf.merge(ids[4], ids[3]);
+        let before = uf.vec.clone();
+        assert_eq!(
+            uf.iter().collect::<Vec<_>>(),
+            vec![
+                (ids[0], ids[0]),
+                (ids[1], ids[1]),
+                (ids[2], ids[2]),
+                (ids[3], ids[1]),
+                (ids[4], ids[1]),
+            ]
+        );
+        assert_eq!(uf.vec, before);
+        for (id, rep) in uf.iter() {
+            assert_eq!(uf.find(id), rep);
+        }
+    }
+
+    #[test]
+    fn depth() {
+        let mut uf = UnionFind::new();
+        let ids: Vec<_> = (0..6).map(|_| uf.makeset()).collect();
+        for i in 1..5 {
+            uf.set_parent(ids[i], ids[i - 1]);
+        }
+        let depths: Vec<_> = ids.iter().map(|id| uf.depth(*id)).collect();
+        assert_eq!(depths, vec![0, 1, 2, 3, 4, 0]);
+        assert_eq!(uf.find_no_compress(ids[4]), ids[0]);
+        assert_eq!(uf.depth(ids[4]), 4);
+        assert_eq!(uf.find(ids[4]), ids[0]);
+        let depths: Vec<_> = ids.iter().map(|id| uf.depth(*id)).collect();
+        assert_eq!(depths, vec![0, 1, 1, 2, 2, 0]);
+    }
+
+    #[test]
+    fn reset() {
+        for policy in [RepresentativePolicy::MinId, RepresentativePolicy::ByRank] {
+            let mut uf = UnionFind::new_with_policy(policy);
+            let ids: Vec<_> = (0..5).map(|_| uf.makeset()).collect();
+            uf.merge_all(&ids[0..3]);
+            uf.merge(ids[3], ids[4]);
+            uf.reset(&ids[0..3]);
+            for id in &ids[0..3] {
+                assert_eq!(uf.find(*id), *id);
+            }
+            assert_eq!(uf.find(ids[3]), uf.find(ids[4]));
+            uf.merge(ids[2], ids[1]);
+            assert_eq!(uf.find(ids[1]), uf.find(ids[2]));
+            assert_ne!(uf.find(ids[0]), uf.find(ids[1]));
+        }
+    }
+
+    #[test]
+    fn invalid_class() {
+        assert_eq!(ClassId::INVALID.idx(), u32::MAX);
+        assert!(!ClassId::INVALID.is_valid());
+        assert!(ClassId::new(0).is_valid());
+        assert!(ClassId::new(u32::MAX - 1).is_valid());
+        assert_eq!(ClassId::new(u32::MAX), ClassId::INVALID);
+        assert!(ClassId::default().is_valid());
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn find_invalid_class() {
+        let mut uf = UnionFind::new();
+        uf.makeset();
+        uf.find(ClassId::INVALID);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn explicit_uf() {
+        let mut uf = ExplicitUnionFind::new();
+        let x = uf.makeset();
+        let y = uf.makeset();
+        let z = uf.makeset();
+        let w = uf.makeset();
+        assert_eq!(uf.merge(y, z), y);
+        assert_eq!(uf.merge(z, x), x);
+        assert_eq!(uf.find(x), x);
+        assert_eq!(uf.find(y), x);
+        assert_eq!(uf.find(z), x);
+        assert_eq!(uf.find(w), w);
+        assert_eq!(uf.members(z), &[x, y, z]);
+
+        assert_eq!(uf.split_off(y), x);
+        assert_eq!(uf.find(y), y);
+        assert_eq!(uf.members(y), &[y]);
+        assert_eq!(uf.find(z), x);
+        assert_eq!(uf.members(x), &[x, z]);
+
+        assert_eq!(uf.split_off(x), z);
+        assert_eq!(uf.find(x), x);
+        assert_eq!(uf.find(z), z);
+        assert_eq!(uf.members(z), &[z]);
+        assert_eq!(uf.split_off(w), w);
+        assert_eq!(uf.find(w), w);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn representative_policy() {
+        let mut uf = UnionFind::new_with_policy(RepresentativePolicy::MinId);
+        let ids: Vec<_> = (0..64).map(|_| uf.makeset()).collect();
+        let mut state = 12345u32;
+        for _ in 0..48 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let x = ids[(state >> 16) as usize % ids.len()];
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let y = ids[(state >> 16) as usize % ids.len()];
+            uf.merge(x, y);
+            for id in &ids {
+                let min = ids
+                    .iter()
+                    .filter(|other| uf.find(**other) == uf.find(*id))
+                    .min()
+                    .unwrap();
+                assert_eq!(uf.find(*id), *min);
+            }
+        }
+
+        let mut uf = UnionFind::new_with_policy(RepresentativePolicy::ByRank);
+        assert_eq!(uf.policy(), RepresentativePolicy::ByRank);
+        let ids: Vec<_> = (0..4).map(|_| uf.makeset()).collect();
+        let range = uf.makeset_many(2);
+        assert_eq!(uf.merge(ids[2], ids[3]), ids[2]);
+        assert_eq!(uf.merge(ids[1], ids[2]), ids[2]);
+        assert_eq!(uf.merge(ids[0], ids[1]), ids[2]);
+        assert_eq!(
+            uf.merge(ClassId(range.start), ClassId(range.end - 1)),
+            ClassId(4)
+        );
+        assert_eq!(uf.merge(ids[0], ClassId(4)), ids[2]);
+        for idx in 0..6 {
+            assert_eq!(uf.find(ClassId(idx)), ids[2]);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn makeset_many() {
+        let mut uf = UnionFind::new();
+        let x = uf.makeset();
+        let range = uf.makeset_many(100);
+        assert_eq!(range, 1..101);
+        assert_eq!(uf.num_classes(), 101);
+        for idx in range.clone() {
+            assert_eq!(uf.find(ClassId::new(idx)), ClassId::new(idx));
+        }
+        assert_eq!(uf.makeset(), ClassId::new(101));
+        assert_eq!(uf.makeset_many(0), 102..102);
+        uf.merge(x, ClassId::new(50));
+        assert_eq!(uf.find(ClassId::new(50)), x);
+    }
+
+    #[test]
+    fn from_pairs() {
+        let uf = UnionFind::from_pairs(
+            7,
+            &[
+                (ClassId(4), ClassId(2)),
+                (ClassId(6), ClassId(5)),
+                (ClassId(2), ClassId(6)),
+                (ClassId(1), ClassId(3)),
+            ],
+        );
+        assert_eq!(uf.num_classes(), 7);
+        let reps: Vec<_> = (0..7).map(|idx| uf.find(ClassId(idx))).collect();
+        assert_eq!(reps, [0, 1, 2, 1, 2, 2, 2].map(ClassId).to_vec());
+        assert_eq!(
+            UnionFind::from_pairs(3, &[]),
+            UnionFind::new_all_not_equals(3)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn merge_all() {
+        for policy in [RepresentativePolicy::MinId, RepresentativePolicy::ByRank] {
+            let mut uf = UnionFind::new_with_policy(policy);
+            let ids: Vec<_> = (0..10).map(|_| uf.makeset()).collect();
+            assert_eq!(uf.merge_all(&[]), None);
+            assert_eq!(uf.merge_all(&ids[3..4]), Some(ids[3]));
+            let merged = [ids[7], ids[2], ids[5], ids[8]];
+            let rep = uf.merge_all(&merged).unwrap();
+            for id in merged {
+                assert_eq!(uf.find(id), rep);
+            }
+            for id in [ids[0], ids[1], ids[3], ids[4], ids[6], ids[9]] {
+                assert_eq!(uf.find(id), id);
+            }
+            if policy == RepresentativePolicy::MinId {
+                assert_eq!(rep, ids[2]);
+            }
+        }
+    }
 }
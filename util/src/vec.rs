@@ -1,25 +1,54 @@
 use core::borrow::{Borrow, BorrowMut};
+#[cfg(not(miri))]
 use core::cell::Cell;
+#[cfg(miri)]
+use core::cell::UnsafeCell;
 use core::fmt::{Debug, Error, Formatter};
+#[cfg(not(miri))]
 use core::marker::PhantomData;
-use core::mem::{align_of, needs_drop, size_of, take};
+#[cfg(not(miri))]
+use core::mem::{align_of, size_of};
+use core::mem::{needs_drop, take};
 use core::ops::Index;
 use core::ops::IndexMut;
-use core::slice::{SliceIndex, from_raw_parts, from_raw_parts_mut};
+#[cfg(miri)]
+use core::ptr;
+use core::slice::SliceIndex;
+#[cfg(not(miri))]
+use core::slice::{from_raw_parts, from_raw_parts_mut};
+#[cfg(not(miri))]
 use core::sync::atomic::Ordering;
 
-use crate::arena::{Arena, ArenaInternal, MCOMMIT_GRANULARITY};
+#[cfg(miri)]
+use alloc::{vec, vec::Vec};
+
+use crate::arena::Arena;
+#[cfg(not(miri))]
+use crate::arena::{ArenaInternal, MCOMMIT_GRANULARITY};
+
+const DEFAULT_GROWTH: f64 = 2.0;
+
+fn grown_capacity(capacity: usize, growth: f64) -> usize {
+    ((capacity as f64 * growth) as usize).max(capacity + 1)
+}
 
 pub struct ArenaVec<'a, T> {
     contents: &'a mut [T],
     len: usize,
+    growth: f64,
 }
 
 impl<'a, T> ArenaVec<'a, T> {
     pub fn new() -> Self {
+        Self::with_growth(DEFAULT_GROWTH)
+    }
+
+    pub fn with_growth(growth: f64) -> Self {
+        assert!(growth > 1.0, "growth factor must be greater than one");
         Self {
             contents: &mut [],
             len: 0,
+            growth,
         }
     }
 
@@ -27,9 +56,43 @@ impl<'a, T> ArenaVec<'a, T> {
         self.len
     }
 
+    pub fn capacity(&self) -> usize {
+        self.contents.len()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
+
+    pub fn last(&self) -> Option<&T> {
+        self.contents[0..self.len].last()
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        self.contents[0..self.len].last_mut()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.contents[0..self.len]
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.contents[0..self.len]
+    }
+
+    pub fn contains(&self, x: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.as_slice().contains(x)
+    }
+
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.as_mut_slice().sort_unstable();
+    }
 }
 
 impl<'a, T: Default> ArenaVec<'a, T> {
@@ -40,10 +103,10 @@ impl<'a, T: Default> ArenaVec<'a, T> {
             let new_contents = arena.new_slice(if self.contents.is_empty() {
                 4
             } else {
-                self.contents.len() * 2
+                grown_capacity(self.contents.len(), self.growth)
             });
-            for i in 0..self.len {
-                new_contents[i] = take(&mut self.contents[i]);
+            for (new, old) in new_contents.iter_mut().zip(&mut self.contents[..self.len]) {
+                *new = take(old);
             }
             self.contents = new_contents;
             self.contents[self.len] = x;
@@ -59,6 +122,30 @@ impl<'a, T: Default> ArenaVec<'a, T> {
             None
         }
     }
+
+    pub fn swap_remove(&mut self, idx: usize) -> T {
+        assert!(idx < self.len, "swap_remove index out of bounds");
+        self.len -= 1;
+        self.contents.swap(idx, self.len);
+        take(&mut self.contents[self.len])
+    }
+
+    pub fn dedup(&mut self)
+    where
+        T: Ord,
+    {
+        let mut kept = 0;
+        for idx in 0..self.len {
+            if kept == 0 || self.contents[idx] != self.contents[kept - 1] {
+                self.contents.swap(kept, idx);
+                kept += 1;
+            }
+        }
+        for idx in kept..self.len {
+            take(&mut self.contents[idx]);
+        }
+        self.len = kept;
+    }
 }
 
 impl<'a, T> AsRef<[T]> for ArenaVec<'a, T> {
@@ -118,22 +205,44 @@ impl<'a, T> Default for ArenaVec<'a, T> {
     }
 }
 
+#[cfg(not(miri))]
 pub struct VirtualVec<T> {
     arena: ArenaInternal<'static>,
     len: Cell<usize>,
+    growth: f64,
     _phantom: PhantomData<T>,
 }
 
+// Miri can't model the reserve-then-commit virtual memory trick, so under miri `VirtualVec` keeps
+// its elements in heap buffers. A full buffer is copied into one grown by `growth`, but stays
+// allocated until the vector is dropped, which is what keeps `static_ref`s and slices handed out
+// before a `push` valid.
+#[cfg(miri)]
+pub struct VirtualVec<T> {
+    buffers: UnsafeCell<Vec<Vec<T>>>,
+    growth: f64,
+}
+
+#[cfg(miri)]
+const INITIAL_CAPACITY: usize = 4;
+
+#[cfg(not(miri))]
 impl<T> VirtualVec<T> {
     pub fn new() -> Self {
+        Self::with_growth(DEFAULT_GROWTH)
+    }
+
+    pub fn with_growth(growth: f64) -> Self {
         const {
             assert!(!needs_drop::<T>());
         }
+        assert!(growth > 1.0, "growth factor must be greater than one");
         let arena = ArenaInternal::new_virt(align_of::<T>());
         unsafe { arena.alloc_assume_aligned(MCOMMIT_GRANULARITY) };
         Self {
             arena,
             len: Cell::new(0),
+            growth,
             _phantom: PhantomData,
         }
     }
@@ -142,6 +251,10 @@ impl<T> VirtualVec<T> {
         self.len.get()
     }
 
+    pub fn capacity(&self) -> usize {
+        self.arena.offset.load(Ordering::Relaxed) / size_of::<T>().max(1)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.len.get() == 0
     }
@@ -150,9 +263,10 @@ impl<T> VirtualVec<T> {
         let old_len = self.len.get();
         unsafe {
             assert!(old_len * size_of::<T>() <= self.arena.offset.load(Ordering::Relaxed));
-            if old_len * size_of::<T>() == self.arena.offset.load(Ordering::Relaxed) {
-                self.arena
-                    .alloc_assume_aligned(self.arena.offset.load(Ordering::Relaxed));
+            let offset = self.arena.offset.load(Ordering::Relaxed);
+            if old_len * size_of::<T>() == offset {
+                let grown = grown_capacity(offset, self.growth).max(offset + size_of::<T>());
+                self.arena.alloc_assume_aligned(grown - offset);
             }
             (self.arena.ptr as *mut T).add(old_len).write(x);
         }
@@ -170,11 +284,91 @@ impl<T> VirtualVec<T> {
         }
     }
 
+    /// # Safety
+    ///
+    /// The returned reference is only valid while `self` is alive and the element at `idx` isn't
+    /// overwritten, popped, or borrowed mutably. The arena never moves, so later pushes don't
+    /// invalidate it.
     pub unsafe fn static_ref(&self, idx: usize) -> &'static T {
         unsafe { &*(self.arena.ptr as *const T).add(idx) }
     }
 }
 
+#[cfg(miri)]
+impl<T> VirtualVec<T> {
+    pub fn new() -> Self {
+        Self::with_growth(DEFAULT_GROWTH)
+    }
+
+    pub fn with_growth(growth: f64) -> Self {
+        const {
+            assert!(!needs_drop::<T>());
+        }
+        assert!(growth > 1.0, "growth factor must be greater than one");
+        Self {
+            buffers: UnsafeCell::new(vec![Vec::with_capacity(INITIAL_CAPACITY)]),
+            growth,
+        }
+    }
+
+    fn vec(&self) -> &Vec<T> {
+        // SAFETY: `push` is the only method that mutates `buffers` through a shared reference,
+        // and it doesn't hold on to anything borrowed from it.
+        unsafe { (*self.buffers.get()).last().unwrap() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec().len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.vec().capacity()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec().is_empty()
+    }
+
+    pub fn push(&self, x: T) {
+        // SAFETY: borrows handed out by `self` point into the buffers' heap allocations, which
+        // this neither moves nor frees. It only writes past the end of the last buffer, or into a
+        // new buffer.
+        let buffers = unsafe { &mut *self.buffers.get() };
+        let full = buffers.last().unwrap();
+        if full.len() == full.capacity() {
+            let mut grown = Vec::with_capacity(grown_capacity(full.capacity(), self.growth));
+            // SAFETY: `grown` has room for every element of `full`, and `T` has no drop glue, so
+            // the copies left behind in `full` are never dropped twice.
+            unsafe {
+                ptr::copy_nonoverlapping(full.as_ptr(), grown.as_mut_ptr(), full.len());
+                grown.set_len(full.len());
+            }
+            buffers.push(grown);
+        }
+        buffers.last_mut().unwrap().push(x);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.buffers.get_mut().last_mut().unwrap().pop()
+    }
+
+    /// # Safety
+    ///
+    /// The returned reference is only valid while `self` is alive and the element at `idx` isn't
+    /// overwritten, popped, or borrowed mutably. Buffers outgrown by later pushes stay allocated,
+    /// so those pushes don't invalidate it.
+    pub unsafe fn static_ref(&self, idx: usize) -> &'static T {
+        unsafe { &*self.vec().as_ptr().add(idx) }
+    }
+}
+
+#[cfg(not(miri))]
+impl<T> Drop for VirtualVec<T> {
+    fn drop(&mut self) {
+        unsafe { self.arena.unmap() };
+    }
+}
+
 impl<T> AsRef<[T]> for VirtualVec<T> {
     fn as_ref(&self) -> &[T] {
         self.borrow()
@@ -187,18 +381,34 @@ impl<T> AsMut<[T]> for VirtualVec<T> {
     }
 }
 
+#[cfg(not(miri))]
 impl<T> Borrow<[T]> for VirtualVec<T> {
     fn borrow(&self) -> &[T] {
         unsafe { from_raw_parts(self.arena.ptr as *const T, self.len.get()) }
     }
 }
 
+#[cfg(not(miri))]
 impl<T> BorrowMut<[T]> for VirtualVec<T> {
     fn borrow_mut(&mut self) -> &mut [T] {
         unsafe { from_raw_parts_mut(self.arena.ptr as *mut T, self.len.get()) }
     }
 }
 
+#[cfg(miri)]
+impl<T> Borrow<[T]> for VirtualVec<T> {
+    fn borrow(&self) -> &[T] {
+        self.vec()
+    }
+}
+
+#[cfg(miri)]
+impl<T> BorrowMut<[T]> for VirtualVec<T> {
+    fn borrow_mut(&mut self) -> &mut [T] {
+        self.buffers.get_mut().last_mut().unwrap()
+    }
+}
+
 impl<T, I: SliceIndex<[T]>> Index<I> for VirtualVec<T> {
     type Output = I::Output;
 
@@ -223,6 +433,8 @@ impl<T> Default for VirtualVec<T> {
 mod tests {
     use super::*;
 
+    use crate::union_find::ClassId;
+
     #[test]
     fn arena_vec() {
         let mut buf: [u64; 64] = [0; 64];
@@ -249,7 +461,132 @@ mod tests {
     }
 
     #[test]
-    #[cfg_attr(miri, ignore)]
+    fn arena_vec_swap_remove() {
+        let mut buf: [u64; 64] = [0; 64];
+        let arena = Arena::new_backed(&mut buf);
+        let mut vec = ArenaVec::new();
+        assert_eq!(vec.last(), None);
+        assert_eq!(vec.last_mut(), None);
+        for x in [1, 2, 3, 4, 5] {
+            vec.push(&arena, x);
+        }
+        assert_eq!(vec.last(), Some(&5));
+        *vec.last_mut().unwrap() = 6;
+        assert_eq!(vec.swap_remove(1), 2);
+        assert_eq!(vec.as_ref(), &[1, 6, 3, 4]);
+        assert_eq!(vec.swap_remove(3), 4);
+        assert_eq!(vec.as_ref(), &[1, 6, 3]);
+        assert_eq!(vec.last(), Some(&3));
+        assert_eq!(vec.swap_remove(0), 1);
+        assert_eq!(vec.swap_remove(0), 3);
+        assert_eq!(vec.swap_remove(0), 6);
+        assert!(vec.is_empty());
+        assert_eq!(vec.last(), None);
+    }
+
+    #[test]
+    fn arena_vec_slices() {
+        let mut buf: [u64; 64] = [0; 64];
+        let arena = Arena::new_backed(&mut buf);
+        let mut vec = ArenaVec::new();
+        assert_eq!(vec.as_slice(), &[]);
+        assert_eq!(vec.as_mut_slice(), &mut []);
+        assert!(!vec.contains(&1));
+        for x in [1, 2, 3] {
+            vec.push(&arena, x);
+        }
+        assert_eq!(vec.as_slice(), &[1, 2, 3]);
+        vec.as_mut_slice()[1] = 5;
+        vec.as_mut_slice().reverse();
+        assert_eq!(vec.as_slice(), &[3, 5, 1]);
+        assert!(vec.contains(&5));
+        assert!(!vec.contains(&2));
+        vec.pop();
+        assert!(!vec.contains(&1));
+        assert_eq!(vec.as_mut_slice(), &mut [3, 5]);
+    }
+
+    #[test]
+    fn arena_vec_sort_dedup() {
+        let mut buf: [u64; 64] = [0; 64];
+        let arena = Arena::new_backed(&mut buf);
+        let mut vec = ArenaVec::new();
+        vec.sort();
+        vec.dedup();
+        assert!(vec.is_empty());
+        for x in [5, 2, 7, 2, 5, 5, 0, 7] {
+            vec.push(&arena, ClassId::new(x));
+        }
+        vec.dedup();
+        assert_eq!(vec.len(), 7);
+        vec.sort();
+        assert_eq!(
+            vec.as_slice(),
+            [0, 2, 2, 5, 5, 7, 7].map(ClassId::new).as_slice()
+        );
+        vec.dedup();
+        assert_eq!(vec.as_slice(), [0, 2, 5, 7].map(ClassId::new).as_slice());
+        assert_eq!(vec.len(), 4);
+        vec.push(&arena, ClassId::new(1));
+        vec.sort();
+        vec.dedup();
+        assert_eq!(vec.as_slice(), [0, 1, 2, 5, 7].map(ClassId::new).as_slice());
+    }
+
+    #[test]
+    fn arena_vec_growth() {
+        let mut buf: [u64; 128] = [0; 128];
+        let arena = Arena::new_backed(&mut buf);
+        let mut vec = ArenaVec::with_growth(1.5);
+        let mut capacities = vec![];
+        for x in 0..40 {
+            vec.push(&arena, x);
+            if capacities.last() != Some(&vec.capacity()) {
+                capacities.push(vec.capacity());
+            }
+        }
+        assert_eq!(capacities, vec![4, 6, 9, 13, 19, 28, 42]);
+        assert_eq!(vec.as_ref(), (0..40).collect::<Vec<_>>());
+
+        let mut vec = ArenaVec::new();
+        for x in 0..9 {
+            vec.push(&arena, x);
+        }
+        assert_eq!(vec.capacity(), 16);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn virtual_vec_growth() {
+        let vec = VirtualVec::<u64>::with_growth(1.5);
+        let initial = vec.capacity();
+        assert_eq!(initial, MCOMMIT_GRANULARITY / size_of::<u64>());
+        for x in 0..=initial as u64 {
+            vec.push(x);
+        }
+        assert_eq!(vec.capacity(), initial * 3 / 2);
+        assert_eq!(vec[initial], initial as u64);
+
+        let vec = VirtualVec::<u64>::new();
+        for x in 0..=initial as u64 {
+            vec.push(x);
+        }
+        assert_eq!(vec.capacity(), initial * 2);
+    }
+
+    #[test]
+    #[cfg(miri)]
+    fn virtual_vec_growth() {
+        let vec = VirtualVec::<u64>::with_growth(1.5);
+        assert_eq!(vec.capacity(), INITIAL_CAPACITY);
+        for x in 0..=INITIAL_CAPACITY as u64 {
+            vec.push(x);
+        }
+        assert_eq!(vec.capacity(), INITIAL_CAPACITY * 3 / 2);
+        assert_eq!(vec.as_ref(), &[0, 1, 2, 3, 4]);
+    }
+
+    #[test]
     fn virtual_vec() {
         let mut vec = VirtualVec::new();
         assert_eq!(vec.as_ref(), &[]);
@@ -271,4 +608,17 @@ mod tests {
         assert_eq!(vec.as_ref(), &[1, 24, 4, 5, 7, 8]);
         assert_eq!(vec.len(), 6);
     }
+
+    #[test]
+    fn virtual_vec_static_ref() {
+        let vec = VirtualVec::new();
+        vec.push((1u32, 2u32));
+        let first = unsafe { vec.static_ref(0) };
+        for x in 0..1000 {
+            vec.push((x, x + 1));
+        }
+        assert_eq!(*first, (1, 2));
+        assert_eq!(vec.len(), 1001);
+        assert_eq!(vec[1000], (999, 1000));
+    }
 }
@@ -0,0 +1,75 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use util::arena::Arena;
+use util::interner::{Interner, StringInterner};
+
+// Counting allocations needs a global allocator, which would apply to every test in whichever
+// binary declares it, so these tests get a binary of their own.
+struct CountingAlloc;
+
+std::thread_local! {
+    static NUM_ALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        NUM_ALLOCS.with(|num| num.set(num.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        NUM_ALLOCS.with(|num| num.set(num.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn num_allocs() -> usize {
+    NUM_ALLOCS.with(Cell::get)
+}
+
+#[test]
+fn intern_fmt_reuses_scratch() {
+    let mut buf: [u64; 8] = [0; 8];
+    let arena = Arena::new_backed(&mut buf);
+    let mut interner = StringInterner::new(&arena);
+    let five = 5;
+    let id = interner.intern_fmt(format_args!("cons_{}", five));
+    let before = num_allocs();
+    assert_eq!(interner.intern_fmt(format_args!("cons_{}", five)), id);
+    assert_eq!(num_allocs(), before);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn with_capacity() {
+    let mut buf: [u64; 4096] = [0; 4096];
+    let arena = Arena::new_backed(&mut buf);
+    let strings: Vec<String> = (0..1000).map(|idx| format!("iden{}", idx)).collect();
+    let mut interner = StringInterner::with_capacity(&arena, strings.len());
+    let before = num_allocs();
+    for string in &strings {
+        interner.intern(string);
+    }
+    assert_eq!(num_allocs(), before);
+    assert_eq!(interner.num_idens(), strings.len());
+    interner.intern("one too many");
+    assert!(num_allocs() > before);
+
+    let mut buf: [u64; 1024] = [0; 1024];
+    let arena = Arena::new_backed(&mut buf);
+    let mut interner = Interner::<u64>::with_capacity(&arena, 500);
+    let before = num_allocs();
+    for obj in 0..500 {
+        interner.intern(obj);
+    }
+    assert_eq!(num_allocs(), before);
+    assert_eq!(interner.num_objs(), 500);
+}
@@ -0,0 +1,15 @@
+use std::process::Command;
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn build_without_default_features() {
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--offline", "-p", "util", "--no-default-features"])
+        .args(["--features", "hashbrown"])
+        .arg("--manifest-path")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .env("CARGO_TARGET_DIR", env!("CARGO_TARGET_TMPDIR"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+}